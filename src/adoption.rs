@@ -0,0 +1,96 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Detect if the simulator never adopted the mappings we created.
+//!
+//! If the simulator starts before the bridge, or uses a different name for a
+//! map, it creates its own anonymous mapping and the `/dev/shm` backed file
+//! stays untouched.
+
+use std::{fs::read, path::PathBuf, thread, time::Duration};
+
+use log::{debug, warn};
+
+use crate::{consumers::has_consumers, shutdown};
+
+/// How the `/dev/shm` backed file of a map looks after the grace period.
+#[derive(Debug, PartialEq, Eq)]
+enum Adoption {
+    /// Another process has mapped the file and wrote some data into it.
+    Adopted,
+    /// No other process has mapped the file.
+    NotMapped,
+    /// Another process has mapped the file but it contains only zeros.
+    Empty,
+}
+
+/// Check if the simulator has adopted the mapping backed by the given file.
+fn adoption(path: &PathBuf) -> Adoption {
+    if !has_consumers(std::slice::from_ref(path)) {
+        Adoption::NotMapped
+    } else if read(path).is_ok_and(|content| content.iter().all(|&byte| byte == 0)) {
+        Adoption::Empty
+    } else {
+        Adoption::Adopted
+    }
+}
+
+/// Spawn a thread which, once the grace period elapsed, checks if the
+/// mappings were adopted and warns about the ones which likely weren't.
+///
+/// The check happens only once and never stops the bridge.
+pub fn watch(maps: Vec<(String, PathBuf)>, grace_period: Duration) {
+    thread::spawn(move || {
+        thread::sleep(grace_period);
+
+        if shutdown::is_requested() {
+            return;
+        }
+
+        let mut failed = false;
+
+        for (name, path) in &maps {
+            match adoption(path) {
+                Adoption::Adopted => debug!("The mapping {name} was adopted"),
+                Adoption::NotMapped => {
+                    warn!(
+                        "No other process has mapped {name} after {} seconds",
+                        grace_period.as_secs_f64()
+                    );
+                    failed = true;
+                }
+                Adoption::Empty => {
+                    warn!(
+                        "The mapping {name} only contains zeros after {} seconds",
+                        grace_period.as_secs_f64()
+                    );
+                    failed = true;
+                }
+            }
+        }
+
+        if failed {
+            warn!(
+                "The simulator likely didn't adopt the mappings. Make sure the bridge is started \
+                 before the simulator and that the map names match the ones the simulator uses."
+            );
+        }
+    });
+}
@@ -0,0 +1,1735 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Create and clean up `/dev/shm` backed file mappings.
+
+use std::{
+    fmt,
+    fs::{hard_link, remove_file, File},
+    io::{self, ErrorKind},
+    os::windows::fs::{MetadataExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, info, log, warn, Level};
+use serde::Serialize;
+use windows::Win32::Storage::FileSystem::{
+    FILE_ATTRIBUTE_ARCHIVE, FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_NORMAL,
+    FILE_ATTRIBUTE_NOT_CONTENT_INDEXED, FILE_ATTRIBUTE_OFFLINE, FILE_ATTRIBUTE_READONLY,
+    FILE_ATTRIBUTE_SYSTEM, FILE_ATTRIBUTE_TEMPORARY,
+};
+
+use crate::{
+    error::{Error, SizeMismatch},
+    file_mapping::{
+        allocation_granularity, existing_size, round_to_page_size, FileMapping, Protection,
+    },
+    map_spec::{Access, MapSpec},
+    output::{self, Event},
+    progress,
+    retry::{Failure, RetryPolicy},
+    shutdown,
+    size::format_size,
+    unix,
+};
+
+/// The attributes the `/dev/shm` backed files are created with by default.
+pub const DEFAULT_FILE_ATTRIBUTES: u32 = FILE_ATTRIBUTE_TEMPORARY.0;
+
+/// The attributes which can be given when a file is created, the rest of the
+/// attributes are either set by the filesystem or need a separate API.
+const SETTABLE_FILE_ATTRIBUTES: u32 = FILE_ATTRIBUTE_READONLY.0
+    | FILE_ATTRIBUTE_HIDDEN.0
+    | FILE_ATTRIBUTE_SYSTEM.0
+    | FILE_ATTRIBUTE_ARCHIVE.0
+    | FILE_ATTRIBUTE_NORMAL.0
+    | FILE_ATTRIBUTE_TEMPORARY.0
+    | FILE_ATTRIBUTE_OFFLINE.0
+    | FILE_ATTRIBUTE_NOT_CONTENT_INDEXED.0;
+
+/// Parse hexadecimal file attributes, with or without a `0x` prefix, i.e.
+/// `0x100` for `FILE_ATTRIBUTE_TEMPORARY`.
+///
+/// Only attributes which can be given when a file is created are accepted.
+/// This is meant to be used as a clap `value_parser`.
+pub fn parse_attributes(input: &str) -> Result<u32, String> {
+    let digits = input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")).unwrap_or(input);
+    let attributes = u32::from_str_radix(digits, 16)
+        .map_err(|e| format!("The attributes `{input}` aren't a valid hexadecimal number: {e}"))?;
+
+    let unsupported = attributes & !SETTABLE_FILE_ATTRIBUTES;
+
+    if unsupported != 0 {
+        return Err(format!(
+            "The attributes `{input}` contain the flags {unsupported:#x}, which can't be set when \
+             creating a file"
+        ));
+    }
+
+    Ok(attributes)
+}
+
+/// What [`create_file_mapping()`] did with the `/dev/shm` backed file of a map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MapStatus {
+    /// The file didn't exist and was created.
+    Created,
+    /// The file already existed and was reused.
+    Reused,
+    /// The file already existed and was replaced, because `--force` was given.
+    Recreated,
+    /// The file was created by a Linux process and was mapped as is.
+    Attached,
+}
+
+impl fmt::Display for MapStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MapStatus::Created => "created",
+            MapStatus::Reused => "reused",
+            MapStatus::Recreated => "recreated (forced)",
+            MapStatus::Attached => "attached",
+        })
+    }
+}
+
+/// Create a named file mapping which is backed by a file at the given path.
+///
+/// The file is created if it doesn't exist yet and is extended to the given
+/// size rounded up to a multiple of the page size, the mapping uses the given
+/// Win32 name and size.
+///
+/// If a mapping with the same name already exists, the creation is retried
+/// according to the given policy before the existing mapping is used.
+///
+/// A read-only mapping only opens the file for writing if it needs to be
+/// created or extended. A new file is created with the given attributes,
+/// usually [`DEFAULT_FILE_ATTRIBUTES`].
+///
+/// Returns the mapping together with [`MapStatus::Created`] if the file didn't
+/// exist yet, or [`MapStatus::Reused`] if it did.
+pub fn create_file_mapping(
+    path: &Path,
+    name: &str,
+    size: usize,
+    protection: Protection,
+    attributes: u32,
+    policy: RetryPolicy,
+) -> Result<(FileMapping, MapStatus)> {
+    // Views of the mapping cover whole pages, extending the file to a multiple of
+    // the page size ensures that the whole view is backed by the file, instead of
+    // relying on how the end of a partially backed page is handled.
+    let file_size = round_to_page_size(size);
+    let existing = existing_size(path)?;
+    let resize = existing != Some(file_size as u64);
+    let status = if existing.is_some() { MapStatus::Reused } else { MapStatus::Created };
+    let writable = protection == Protection::ReadWrite;
+
+    debug!("Opening the tmpfs file {}", path.display());
+
+    // First we create a /dev/shm backed file.
+    //
+    // Now hear me out, usually we should use `shm_open(3)` here, but on Linux
+    // `shm_open()` just calls `open()`. It does have some logic to find the
+    // tmpfs location if it's mounted in a non-standard location. Since we can't
+    // call `shm_open(3)` from inside the Wine environment
+    let mut file = open_tmpfs_file(path, writable || resize, attributes)?;
+
+    if file_size != size {
+        debug!(
+            "Rounding the size of the tmpfs file {} up to the page size, {size} bytes were \
+             requested, the file will have {file_size} bytes",
+            path.display()
+        );
+    }
+
+    if resize {
+        file.set_len(file_size as u64).with_context(|| {
+            format!("Could not extend the tmpfs file {path:?} to {file_size} bytes")
+        })?;
+
+        // Give up the write access again, now that the file has the correct size.
+        if !writable {
+            file = open_tmpfs_file(path, false, attributes)?;
+        }
+    }
+
+    let mapping = map_file(&file, name, size, protection, policy)?;
+    check_mapping_size(path, &file, &mapping);
+
+    Ok((mapping, status))
+}
+
+/// Does a `/dev/shm` backed file with the given length back the whole mapping
+/// of the given size, i.e. is it exactly the mapping size rounded up to the
+/// page size.
+fn sizes_agree(file_len: u64, mapping_size: usize) -> bool {
+    file_len == round_to_page_size(mapping_size) as u64
+}
+
+/// Check that the length of the `/dev/shm` backed file agrees with the size of
+/// the mapping we just created.
+///
+/// The file and the mapping sizes are set separately, since letting the
+/// mapping pick up the size of the file crashes the simulator, see
+/// [`map_file()`]. If they diverge, the simulator accesses memory which isn't
+/// backed by the file, so this is logged as a warning, the comparison itself
+/// is logged with `--verbose`.
+fn check_mapping_size(path: &Path, file: &File, mapping: &FileMapping) {
+    let mapping_size = mapping.size();
+
+    match file.metadata().map(|metadata| metadata.len()) {
+        Ok(len) if sizes_agree(len, mapping_size) => debug!(
+            "The tmpfs file {} has {len} bytes, which agrees with the mapping size of \
+             {mapping_size} bytes",
+            path.display()
+        ),
+        Ok(len) => warn!(
+            "The tmpfs file {} has {len} bytes, but the mapping has {mapping_size} bytes, which \
+             are {} bytes rounded up to the page size, the simulator might crash accessing it",
+            path.display(),
+            round_to_page_size(mapping_size)
+        ),
+        Err(e) => warn!("Could not check the size of the tmpfs file {}: {e}", path.display()),
+    }
+}
+
+/// Map an existing `/dev/shm` backed file, which a Linux process created, to a
+/// named file mapping, without creating or resizing the file.
+///
+/// The mapping has the size of the file, which is returned together with the
+/// mapping. Fails with [`Error::MissingMap`] if the file doesn't exist. A
+/// mapping with the same name is handled like [`create_file_mapping()`] does.
+pub fn attach_file_mapping(
+    path: &Path,
+    name: &str,
+    protection: Protection,
+    policy: RetryPolicy,
+) -> Result<(FileMapping, usize)> {
+    debug!("Attaching to the tmpfs file {}", path.display());
+
+    let file =
+        File::options().read(true).write(protection == Protection::ReadWrite).open(path).map_err(
+            |source| match source.kind() {
+                ErrorKind::NotFound => {
+                    Error::MissingMap { name: name.to_owned(), path: path.to_owned() }.into()
+                }
+                ErrorKind::PermissionDenied => permission_hint(source, path),
+                _ => Error::FileCreate { path: path.to_owned(), source }.into(),
+            },
+        )?;
+
+    let size = file
+        .metadata()
+        .with_context(|| format!("Could not query the size of {}", path.display()))?
+        .len();
+    let size = usize::try_from(size)
+        .with_context(|| format!("The file {} is too large to be mapped", path.display()))?;
+
+    if size == 0 {
+        bail!("The file {} of the map {name} is empty, it can't be attached to", path.display());
+    }
+
+    Ok((map_file(&file, name, size, protection, policy)?, size))
+}
+
+/// Create a named file mapping of the given size which is backed by the given
+/// `/dev/shm` file, retrying if a mapping with the same name already exists.
+fn map_file(
+    file: &File,
+    name: &str,
+    size: usize,
+    protection: Protection,
+    policy: RetryPolicy,
+) -> Result<FileMapping> {
+    // If the simulator is started at the same time as the bridge, it might create
+    // its own mapping right before we create ours. The simulator might close its
+    // mapping again, so we retry a couple of times before settling on the
+    // existing mapping.
+    policy.run(|attempt| {
+        // Now we create a mapping that is backed by the previously created /dev/shm`
+        // file.
+        let mapping = FileMapping::new(
+            // We're going to use the same names the Simulator uses. This ensures that the
+            // simulator will reuse this `/dev/shm` backed mapping instead of creating a new
+            // anonymous one. Making the simulator reuse the mapping in turn means that the
+            // telemetry data will be available in `/dev/shm` as well, making it accessible to
+            // Linux.
+            //
+            // The name may contain a namespace prefix, which the name of the `/dev/shm` file
+            // doesn't.
+            name,
+            // Pass in the handle of the `/dev/shm` file, this ensures that the file mapping is a
+            // file backed one and is using our tmpfs file created on the Linux side.
+            file,
+            // The documentation[1] for CreateFileMapping states that the sizes are only necessary
+            // if we're using a `INVALID_HANDLE_VALUE` for the file handle.
+            //
+            // It also states the following:
+            // > If this parameter and dwMaximumSizeHigh are 0 (zero), the maximum size of the
+            // > file mapping object is equal to the current size of the file that hFile
+            // > identifies.
+            //
+            // This sadly doesn't seem to work with our `/dev/shm` file and makes the Simulator
+            // crash, so we're passing the sizes manually.
+            //
+            // [1]: https://learn.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-createfilemappinga#parameters
+            size, protection,
+        )
+        .map_err(Failure::Permanent)?;
+
+        if !mapping.already_existed() {
+            return Ok(mapping);
+        }
+
+        if attempt.is_last() {
+            // The existing mapping is only compatible if it's at least as large as the
+            // one we wanted to create, otherwise mapping a view fails.
+            mapping
+                .view()
+                .with_context(|| format!("A smaller mapping with the name {name} already exists"))
+                .map_err(Failure::Permanent)?;
+
+            warn!(
+                "A mapping with the name {name} already exists, it was probably created by the \
+                 simulator. Reusing the existing mapping, which might not be backed by the \
+                 /dev/shm file, start the bridge before the simulator to avoid this"
+            );
+
+            return Ok(mapping);
+        }
+
+        drop(mapping);
+
+        info!(
+            "A mapping with the name {name} already exists, retrying in {} ms (attempt {} of {})",
+            attempt.delay.as_millis(),
+            attempt.number,
+            attempt.attempts
+        );
+
+        Err(Failure::Transient(anyhow!("A mapping with the name {name} already exists")))
+    })
+}
+
+/// Open, or create, the tmpfs file at the given path.
+///
+/// Creating the file requires write access, so a missing file is only created
+/// if `writable` is set, using the given attributes.
+fn open_tmpfs_file(path: &Path, writable: bool, attributes: u32) -> Result<File> {
+    File::options()
+        .read(true)
+        .write(writable)
+        .attributes(attributes)
+        .create(writable)
+        .open(path)
+        .map_err(|source| match source.kind() {
+            ErrorKind::PermissionDenied => permission_hint(source, path),
+            _ => Error::FileCreate { path: path.to_owned(), source }.into(),
+        })
+}
+
+/// Add some guidance to an error caused by missing permissions on the shm
+/// directory, other errors are returned as is.
+fn permission_hint(error: io::Error, path: &Path) -> anyhow::Error {
+    if error.kind() != ErrorKind::PermissionDenied {
+        return error.into();
+    }
+
+    Error::PermissionDenied { path: path.to_owned(), source: error }.into()
+}
+
+/// What [`clean_up_summary()`] did with the `/dev/shm` backed files.
+#[derive(Debug, Default)]
+pub struct CleanUpSummary {
+    /// The maps whose files were removed.
+    pub removed: Vec<String>,
+    /// The maps whose files didn't exist, together with the path of the file.
+    pub absent: Vec<(String, PathBuf)>,
+    /// The maps whose files couldn't be removed, together with the reason.
+    pub failed: Vec<(String, anyhow::Error)>,
+}
+
+impl CleanUpSummary {
+    /// Were all the files removed.
+    pub fn is_clean(&self) -> bool {
+        self.absent.is_empty() && self.failed.is_empty()
+    }
+
+    /// Turn the files which were absent or couldn't be removed into an error.
+    ///
+    /// A single absent file is returned as an [`Error::MissingMap`], otherwise
+    /// an [`Error::CleanupFailed`] containing all of the files is returned.
+    pub fn into_result(mut self) -> Result<(), Error> {
+        if self.absent.len() == 1 && self.failed.is_empty() {
+            let (name, path) = self.absent.remove(0);
+            return Err(Error::MissingMap { name, path });
+        }
+
+        let failures: Vec<_> = self
+            .absent
+            .into_iter()
+            .map(|(name, path)| (name.clone(), Error::MissingMap { name, path }.into()))
+            .chain(self.failed)
+            .collect();
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::CleanupFailed { failures })
+        }
+    }
+}
+
+impl fmt::Display for CleanUpSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let removed = self.removed.len();
+        let noun = if removed == 1 { "file" } else { "files" };
+
+        write!(
+            f,
+            "removed {removed} {noun}, {} already absent, {} couldn't be removed",
+            self.absent.len(),
+            self.failed.len()
+        )
+    }
+}
+
+/// Unlink the `/dev/shm` backed files of the given maps, whose file names use
+/// the given prefix, see [`file_name()`](crate::map_spec::file_name).
+///
+/// A failure to remove one file doesn't stop us from removing the rest, the
+/// removal of a file is retried according to the given policy before giving
+/// up on it. The returned summary contains which files were removed,
+/// which were already absent, and which couldn't be removed.
+pub fn clean_up_summary(
+    dir: &Path,
+    prefix: &str,
+    maps: &[MapSpec],
+    policy: RetryPolicy,
+) -> CleanUpSummary {
+    let mut summary = CleanUpSummary::default();
+
+    for map in maps {
+        let name = &map.name;
+        let path = dir.join(map.file_name(prefix));
+        info!("Removing mapping {name} at {}", path.display());
+
+        match remove_map_file(name, &path, policy) {
+            Ok(()) => {
+                output::emit(&Event::MappingRemoved { name, path: &path });
+                summary.removed.push(name.clone());
+            }
+            Err(e) => {
+                warn!("Could not remove the mapping {name}: {e:#}");
+                output::emit(&Event::RemovalFailed {
+                    name,
+                    path: &path,
+                    message: format!("{e:#}"),
+                });
+
+                if matches!(e.downcast_ref(), Some(Error::MissingMap { .. })) {
+                    summary.absent.push((name.clone(), path));
+                } else {
+                    summary.failed.push((name.clone(), e));
+                }
+            }
+        }
+    }
+
+    summary
+}
+
+/// Unlink the `/dev/shm` backed files of the given maps, like
+/// [`clean_up_summary()`] does.
+///
+/// If files are absent or couldn't be removed, an [`Error::CleanupFailed`]
+/// containing all the failures is returned, or an [`Error::MissingMap`] if a
+/// single file was absent, see [`CleanUpSummary::into_result()`].
+pub fn clean_up(
+    dir: &Path,
+    prefix: &str,
+    maps: &[MapSpec],
+    policy: RetryPolicy,
+) -> Result<(), Error> {
+    clean_up_summary(dir, prefix, maps, policy).into_result()
+}
+
+/// Remove the `/dev/shm` backed file of a single map, retrying if the removal
+/// fails, i.e. because the filesystem is busy.
+fn remove_map_file(name: &str, path: &Path, policy: RetryPolicy) -> Result<()> {
+    policy.run(|attempt| match remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Err(Failure::Permanent(
+            Error::MissingMap { name: name.to_owned(), path: path.to_owned() }.into(),
+        )),
+        Err(e) if !attempt.is_last() => {
+            debug!(
+                "Removing {} failed, retrying in {} ms (attempt {} of {}): {e}",
+                path.display(),
+                attempt.delay.as_millis(),
+                attempt.number,
+                attempt.attempts
+            );
+
+            Err(Failure::Transient(e.into()))
+        }
+        Err(e) => Err(Failure::Permanent(
+            permission_hint(e, path)
+                .context(format!("Could not unlink the /dev/shm backed file {name}")),
+        )),
+    })
+}
+
+/// What needs to happen with the `/dev/shm` backed file of a map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileAction {
+    /// The file doesn't exist yet and needs to be created.
+    Create,
+    /// The file already exists with the requested size and can be reused.
+    Reuse,
+    /// The file already exists, but needs to be removed and created again.
+    Recreate,
+}
+
+/// Decide what to do with the `/dev/shm` backed file of a map, given the size
+/// of the existing file, if there is one.
+///
+/// Existing files are reused if they have the requested size, either the
+/// logical `size` of the map, or the `file_size` rounded up to the page size.
+/// A mapping with a different size makes the simulator crash, so such files are
+/// only recreated if `force` is set, `None` is returned otherwise.
+fn file_action(
+    existing: Option<u64>,
+    size: usize,
+    file_size: usize,
+    force: bool,
+) -> Option<FileAction> {
+    match existing {
+        None => Some(FileAction::Create),
+        Some(_) if force => Some(FileAction::Recreate),
+        Some(existing) if existing == size as u64 || existing == file_size as u64 => {
+            Some(FileAction::Reuse)
+        }
+        Some(_) => None,
+    }
+}
+
+/// Check that an existing `/dev/shm` backed file looks like one of ours before
+/// it's reused, so we don't hijack the shared memory of another application.
+///
+/// Files owned by another user are refused, files without the temporary
+/// attribute the bridge sets, if it's part of the given attributes new files
+/// are created with, are only warned about. `--force` recreates the file
+/// instead of reusing it, which skips this check.
+fn check_owner(name: &str, path: &Path, created_with: u32) -> Result<()> {
+    let attributes = std::fs::metadata(path)
+        .with_context(|| format!("Couldn't query the attributes of {}", path.display()))?
+        .file_attributes();
+
+    let owner = match (unix::owner(path), unix::current_uid()) {
+        (Ok((uid, user)), Ok(our_uid)) if uid != our_uid => {
+            bail!(
+                "The existing file {} of the map {name} is owned by {user} ({uid}), not by us \
+                 ({our_uid}), it probably belongs to another application, use --force to replace \
+                 it",
+                path.display()
+            );
+        }
+        (Ok((uid, user)), Ok(_)) => format!("{user} ({uid})"),
+        (Err(e), _) | (_, Err(e)) => {
+            debug!("Couldn't check the owner of {}: {e:#}", path.display());
+            "unknown".to_owned()
+        }
+    };
+
+    // Files created with custom attributes might not have the temporary attribute.
+    let expected = created_with & FILE_ATTRIBUTE_TEMPORARY.0;
+
+    if attributes & expected != expected {
+        warn!(
+            "The existing file {} of the map {name} doesn't look like it was created by the \
+             bridge, its attributes are {attributes:#x} and its owner is {owner}, reusing it \
+             anyway",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Remove the hard links which make the `/dev/shm` backed files of maps
+/// available under another name.
+///
+/// The aliases are given as pairs of the map name and the alias, aliases which
+/// don't exist are skipped. A failure to remove one alias doesn't stop us from
+/// removing the rest, the aliases which couldn't be removed are returned as
+/// pairs of the map name and the reason.
+pub fn clean_up_aliases(dir: &Path, aliases: &[(String, String)]) -> Vec<(String, anyhow::Error)> {
+    let mut failures = Vec::new();
+
+    for (name, alias) in aliases {
+        let path = dir.join(alias);
+        info!("Removing the alias {alias} of the mapping {name} at {}", path.display());
+
+        match remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                debug!("The alias {} doesn't exist", path.display());
+            }
+            Err(e) => {
+                let e = permission_hint(e, &path)
+                    .context(format!("Could not remove the alias {alias} of {name}"));
+                warn!("{e:#}");
+
+                output::emit(&Event::RemovalFailed {
+                    name,
+                    path: &path,
+                    message: format!("{e:#}"),
+                });
+                failures.push((name.clone(), e));
+            }
+        }
+    }
+
+    failures
+}
+
+/// A set of `/dev/shm` backed file mappings which are created and cleaned up
+/// together.
+///
+/// The mappings stay alive as long as the [`Bridge`] does, or until
+/// [`Bridge::cleanup()`] is called. Once started, dropping the [`Bridge`]
+/// removes the `/dev/shm` backed files as well, unless
+/// [`Bridge::keep_files()`] is set, so the files don't leak if we panic. The
+/// same goes for the aliases added using [`Bridge::add_alias()`].
+pub struct Bridge {
+    /// The directory the `/dev/shm` backed files are placed in.
+    dir: PathBuf,
+    /// The prefix of the names of the `/dev/shm` backed files.
+    prefix: String,
+    /// The attributes newly created `/dev/shm` backed files should have.
+    attributes: u32,
+    /// The maps which should be created.
+    maps: Vec<MapSpec>,
+    /// Should existing files be removed instead of being reused.
+    force: bool,
+    /// Should the content of recreated files be copied into the new files.
+    migrate: bool,
+    /// Should newly created mappings be overwritten with zeros.
+    zero: bool,
+    /// The permission bits newly created files should have.
+    mode: Option<u32>,
+    /// The group ID newly created files should belong to.
+    gid: Option<u32>,
+    /// The number of extra bytes allocated after the end of every map.
+    padding: usize,
+    /// Should the files be checked after all the mappings were created.
+    verify: bool,
+    /// Should the files be left in place when the bridge is dropped.
+    keep_files: bool,
+    /// Should the status message about reused files be a debug message.
+    quiet_on_reuse: bool,
+    /// Should the remaining maps be created if the mapping of a map can't be
+    /// created.
+    best_effort: bool,
+    /// Should the bridge refuse to start if any existing file has the wrong
+    /// size.
+    strict_sizes: bool,
+    /// Should the mappings be flushed after they were written to.
+    sync: bool,
+    /// How the creation of the mappings and the removal of the files are
+    /// retried.
+    retry_policy: RetryPolicy,
+    /// The maps whose mappings couldn't be created in the best-effort mode.
+    failures: Vec<(String, anyhow::Error)>,
+    /// Additional names, as pairs of the map name and the alias, under which
+    /// the files of maps should be available.
+    aliases: Vec<(String, String)>,
+    /// The names of the maps which should be read-only.
+    read_only: Vec<String>,
+    /// The names of the maps whose existing files are mapped as is.
+    attached: Vec<String>,
+    /// The addresses the views of maps should be mapped at, as pairs of the
+    /// map name and the address.
+    base_addresses: Vec<(String, usize)>,
+    /// The hard links which were created by [`Bridge::start()`].
+    links: Vec<PathBuf>,
+    /// The mappings which were created by [`Bridge::start()`].
+    mappings: Vec<FileMapping>,
+    /// What [`Bridge::start()`] did with the file of every map.
+    statuses: Vec<(String, MapStatus)>,
+}
+
+impl Bridge {
+    /// Create a new bridge which places the `/dev/shm` backed files in the
+    /// given directory.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            prefix: String::new(),
+            attributes: DEFAULT_FILE_ATTRIBUTES,
+            maps: Vec::new(),
+            force: false,
+            migrate: false,
+            zero: true,
+            mode: None,
+            gid: None,
+            padding: 0,
+            verify: false,
+            keep_files: false,
+            quiet_on_reuse: false,
+            best_effort: false,
+            strict_sizes: false,
+            sync: false,
+            retry_policy: RetryPolicy::default(),
+            failures: Vec::new(),
+            aliases: Vec::new(),
+            read_only: Vec::new(),
+            attached: Vec::new(),
+            base_addresses: Vec::new(),
+            links: Vec::new(),
+            mappings: Vec::new(),
+            statuses: Vec::new(),
+        }
+    }
+
+    /// Add a map which should be created once the bridge is started.
+    pub fn add_map(&mut self, name: impl Into<String>, size: usize) -> &mut Self {
+        self.maps.push(MapSpec::new(name, size));
+        self
+    }
+
+    /// Make the `/dev/shm` backed file of the map with the given name available
+    /// under another file name as well, using a hard link.
+    pub fn add_alias(&mut self, name: impl Into<String>, alias: impl Into<String>) -> &mut Self {
+        self.aliases.push((name.into(), alias.into()));
+        self
+    }
+
+    /// Create the mapping of the map with the given name with read-only
+    /// protection, for maps which are only written to through the `/dev/shm`
+    /// backed file.
+    pub fn add_read_only(&mut self, name: impl Into<String>) -> &mut Self {
+        self.read_only.push(name.into());
+        self
+    }
+
+    /// Map the existing `/dev/shm` backed file of the map with the given name
+    /// as is, for files a Linux process creates before the bridge starts.
+    ///
+    /// The file isn't created, resized, zeroed, or removed by the bridge, the
+    /// size of the map is replaced by the size of the file once the bridge is
+    /// started. Starting the bridge fails if the file doesn't exist.
+    pub fn attach(&mut self, name: impl Into<String>) -> &mut Self {
+        self.attached.push(name.into());
+        self
+    }
+
+    /// Map the views of the map with the given name at the given address, this
+    /// takes precedence over the base address of the [`MapSpec`].
+    ///
+    /// This only affects the views the bridge maps, see
+    /// [`FileMapping::set_base_address()`]. The mapping of the map fails to be
+    /// created if a view can't be mapped at the address.
+    pub fn base_address(&mut self, name: impl Into<String>, address: usize) -> &mut Self {
+        self.base_addresses.push((name.into(), address));
+        self
+    }
+
+    /// Prepend the given prefix to the names of the `/dev/shm` backed files,
+    /// see [`file_name()`](crate::map_spec::file_name). The Win32 names of the
+    /// maps aren't affected.
+    pub fn prefix(&mut self, prefix: impl Into<String>) -> &mut Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Remove the `/dev/shm` backed files of the maps if they already exist,
+    /// instead of reusing them.
+    pub fn force(&mut self, force: bool) -> &mut Self {
+        self.force = force;
+        self
+    }
+
+    /// Copy the content of the existing files, which are recreated because
+    /// [`Bridge::force()`] is set, into the new files.
+    pub fn migrate(&mut self, migrate: bool) -> &mut Self {
+        self.migrate = migrate;
+        self
+    }
+
+    /// Overwrite the content of newly created maps with zeros, this is enabled
+    /// by default.
+    pub fn zero(&mut self, zero: bool) -> &mut Self {
+        self.zero = zero;
+        self
+    }
+
+    /// Set the permission bits newly created `/dev/shm` backed files should
+    /// have.
+    pub fn mode(&mut self, mode: Option<u32>) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the group ID newly created `/dev/shm` backed files should belong to.
+    pub fn group(&mut self, gid: Option<u32>) -> &mut Self {
+        self.gid = gid;
+        self
+    }
+
+    /// Create the `/dev/shm` backed files with the given attributes instead of
+    /// [`DEFAULT_FILE_ATTRIBUTES`].
+    ///
+    /// This is meant for debugging differences in how Wine versions treat the
+    /// files, other attributes aren't supported.
+    pub fn file_attributes(&mut self, attributes: u32) -> &mut Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Allocate the given number of extra bytes after the end of every map, the
+    /// padding is part of the file as well as of the mapping.
+    pub fn padding(&mut self, padding: usize) -> &mut Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Check that the `/dev/shm` backed files have the expected size and are
+    /// readable, once all the mappings were created.
+    pub fn verify(&mut self, verify: bool) -> &mut Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Don't remove the `/dev/shm` backed files when the bridge is dropped,
+    /// they can still be removed using [`Bridge::cleanup()`].
+    pub fn keep_files(&mut self, keep_files: bool) -> &mut Self {
+        self.keep_files = keep_files;
+        self
+    }
+
+    /// Only log reused `/dev/shm` backed files at the debug level, for launches
+    /// which rely on the files being reused.
+    pub fn quiet_on_reuse(&mut self, quiet_on_reuse: bool) -> &mut Self {
+        self.quiet_on_reuse = quiet_on_reuse;
+        self
+    }
+
+    /// Keep creating the remaining maps if the mapping of a map can't be
+    /// created, instead of rolling back.
+    ///
+    /// The maps which failed are removed from the bridge, see
+    /// [`Bridge::take_failures()`]. Starting the bridge only fails if none of
+    /// the mappings could be created, or if verifying the files or creating
+    /// the aliases fails.
+    pub fn best_effort(&mut self, best_effort: bool) -> &mut Self {
+        self.best_effort = best_effort;
+        self
+    }
+
+    /// Flush the views of the mappings after they were zeroed or initialized,
+    /// so the content is written back to the files before the simulator
+    /// attaches. Mappings which weren't written to aren't flushed.
+    pub fn sync(&mut self, sync: bool) -> &mut Self {
+        self.sync = sync;
+        self
+    }
+
+    /// Retry the creation of a mapping whose name is already taken, and the
+    /// removal of a `/dev/shm` backed file, according to the given policy,
+    /// [`RetryPolicy::default()`] is used otherwise.
+    pub fn retry_policy(&mut self, retry_policy: RetryPolicy) -> &mut Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Refuse to start if the `/dev/shm` backed file of any map already exists
+    /// with a different size than requested, instead of failing on the first
+    /// such map or recreating the files with [`Bridge::force()`].
+    ///
+    /// All the mismatched maps are reported in a single
+    /// [`Error::StrictSizeMismatch`] before any mapping is created.
+    pub fn strict_sizes(&mut self, strict_sizes: bool) -> &mut Self {
+        self.strict_sizes = strict_sizes;
+        self
+    }
+
+    /// Take the maps whose mappings couldn't be created with
+    /// [`Bridge::best_effort()`], together with the reason.
+    pub fn take_failures(&mut self) -> Vec<(String, anyhow::Error)> {
+        std::mem::take(&mut self.failures)
+    }
+
+    /// The directory the `/dev/shm` backed files are placed in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The path of the `/dev/shm` backed file of the given map.
+    pub fn file_path(&self, map: &MapSpec) -> PathBuf {
+        self.dir.join(map.file_name(&self.prefix))
+    }
+
+    /// The maps the bridge manages.
+    pub fn maps(&self) -> &[MapSpec] {
+        &self.maps
+    }
+
+    /// What [`Bridge::start()`] did with the `/dev/shm` backed file of every
+    /// map, as pairs of the map name and the status.
+    pub fn statuses(&self) -> &[(String, MapStatus)] {
+        &self.statuses
+    }
+
+    /// Create the file mappings for all the maps.
+    ///
+    /// If one of the mappings can't be created, a shutdown is requested while
+    /// the mappings are being created, or the verification of the files fails,
+    /// the `/dev/shm` backed files we created so far are removed again, files
+    /// that existed before are left alone. See [`Bridge::best_effort()`] for
+    /// creating as many of the mappings as possible instead.
+    pub fn start(&mut self) -> Result<()> {
+        for map in self.maps.iter_mut().filter(|map| self.attached.contains(&map.name)) {
+            let path = self.dir.join(map.file_name(&self.prefix));
+
+            map.size = existing_size(&path)?
+                .ok_or_else(|| Error::MissingMap { name: map.name.clone(), path })?
+                .try_into()
+                .with_context(|| format!("The file of the map {} is too large", map.name))?;
+        }
+
+        if self.strict_sizes {
+            self.check_sizes()?;
+        }
+
+        let mut mappings = Vec::with_capacity(self.maps.len());
+        let mut statuses = Vec::with_capacity(self.maps.len());
+        let mut created = Vec::new();
+        let mut failures = Vec::new();
+
+        let total = self.maps.len();
+
+        for (number, map) in self.maps.iter().enumerate() {
+            progress::update(&format!(
+                "Creating map {}/{total}: {} ({})...",
+                number + 1,
+                map.name,
+                format_size(map.size as u64)
+            ));
+
+            let result = if shutdown::is_requested() {
+                Err(anyhow!("The creation of the mappings was interrupted"))
+            } else {
+                self.create_mapping(map, &mut created)
+            };
+
+            match result {
+                Ok((mapping, status)) => {
+                    mappings.push(mapping);
+                    statuses.push((map.name.clone(), status));
+                }
+                Err(e) if self.best_effort && !shutdown::is_requested() => {
+                    warn!(
+                        "Creating the mapping {} failed, continuing with the rest: {e:#}",
+                        map.name
+                    );
+
+                    // Only the file of the failed map is removed, if we created it.
+                    if let Some(index) = created.iter().position(|created| created == map) {
+                        self.roll_back(vec![created.remove(index)]);
+                    }
+
+                    failures.push((map.name.clone(), e));
+                }
+                Err(e) => {
+                    progress::clear();
+                    warn!("Creating the mapping {} failed, rolling back", map.name);
+
+                    drop(mappings);
+                    self.roll_back(created);
+
+                    return Err(e);
+                }
+            }
+        }
+
+        progress::finish(&format!("Done creating the mappings ({}/{total})", mappings.len()));
+
+        if !failures.is_empty() {
+            if mappings.is_empty() {
+                return Err(Error::CreateFailed { failures }.into());
+            }
+
+            // The rest of the bridge only deals with the maps which exist.
+            self.maps.retain(|map| !failures.iter().any(|(name, _)| name == &map.name));
+            self.aliases.retain(|(name, alias)| {
+                let exists = self.maps.iter().any(|map| &map.name == name);
+
+                if !exists {
+                    warn!(
+                        "Skipping the alias {alias} since the mapping {name} couldn't be created"
+                    );
+                }
+
+                exists
+            });
+            self.failures = failures;
+        }
+
+        if self.verify {
+            for map in &self.maps {
+                if let Err(e) = self.verify_mapping(map) {
+                    warn!("Verifying the mapping {} failed, rolling back", map.name);
+
+                    drop(mappings);
+                    self.roll_back(created);
+
+                    return Err(e);
+                }
+            }
+        }
+
+        match self.create_aliases() {
+            Ok(links) => self.links = links,
+            Err(e) => {
+                warn!("Creating the aliases failed, rolling back");
+
+                drop(mappings);
+                self.roll_back(created);
+
+                return Err(e);
+            }
+        }
+
+        // Only now the mappings own their files, a rollback needs to leave the files
+        // which existed before alone.
+        if !self.keep_files {
+            for (map, mapping) in self.maps.iter().zip(&mut mappings) {
+                if !self.attached.contains(&map.name) {
+                    mapping.own_file(self.file_path(map));
+                }
+            }
+        }
+
+        self.mappings = mappings;
+        self.statuses = statuses;
+
+        Ok(())
+    }
+
+    /// Check that the `/dev/shm` backed files of the maps still exist with the
+    /// expected size, and recreate the mappings whose files vanished or were
+    /// resized by another process.
+    ///
+    /// Our handle of the old mapping is closed first, so the old named mapping
+    /// goes away and the new one is backed by the new file. If the simulator
+    /// still holds a handle of the old mapping, the new mapping can't replace
+    /// it, this is logged as a warning. A map whose mapping can't be recreated
+    /// isn't managed by the bridge anymore, its aliases are removed as well.
+    ///
+    /// Returns the number of recreated mappings, or an
+    /// [`Error::CreateFailed`] containing the maps which were dropped because
+    /// their mappings couldn't be recreated.
+    pub fn repair(&mut self) -> Result<usize> {
+        let mut recreated = 0;
+        let mut failures = Vec::new();
+        let mut index = 0;
+
+        while index < self.maps.len() {
+            let map = self.maps[index].clone();
+            let path = self.file_path(&map);
+            let attached = self.attached.contains(&map.name);
+            let allocated = if attached { map.size } else { map.size.saturating_add(self.padding) };
+
+            let intact = match existing_size(&path) {
+                Ok(Some(len)) => {
+                    len == allocated as u64 || len == round_to_page_size(allocated) as u64
+                }
+                Ok(None) => false,
+                // We can't tell, so let's not touch the file.
+                Err(e) => {
+                    debug!("{e:#}");
+                    true
+                }
+            };
+
+            if intact {
+                index += 1;
+                continue;
+            }
+
+            warn!(
+                "The /dev/shm backed file {} of the map {} vanished or was resized, recreating it",
+                path.display(),
+                map.name
+            );
+
+            let mut old = self.mappings.remove(index);
+            old.release_file();
+            drop(old);
+
+            match self.recreate_mapping(&map) {
+                Ok((mapping, status)) => {
+                    // Attached maps follow the size of the new file.
+                    if let Some(len) = existing_size(&path).ok().flatten().filter(|_| attached) {
+                        self.maps[index].size = len as usize;
+                    }
+
+                    self.set_status(&map.name, status);
+                    self.mappings.insert(index, mapping);
+                    recreated += 1;
+                    index += 1;
+                }
+                Err(e) => {
+                    warn!(
+                        "Could not recreate the mapping {}, it isn't managed anymore: {e:#}",
+                        map.name
+                    );
+
+                    // The failures are already logged.
+                    let (_, aliases) = self.forget_map(index);
+                    clean_up_aliases(&self.dir, &aliases);
+
+                    failures.push((map.name, e));
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(recreated)
+        } else {
+            Err(Error::CreateFailed { failures }.into())
+        }
+    }
+
+    /// Update the modification time of the `/dev/shm` backed files of the maps,
+    /// so external tooling can tell the files of a running bridge apart from
+    /// stale ones.
+    ///
+    /// Files which can't be touched are logged as a warning.
+    pub fn touch(&self) {
+        let now = SystemTime::now();
+
+        for map in &self.maps {
+            let path = self.file_path(map);
+
+            let result =
+                File::options().write(true).open(&path).and_then(|file| file.set_modified(now));
+
+            match result {
+                Ok(()) => debug!("Touched the file {}", path.display()),
+                Err(e) => {
+                    warn!("Could not update the modification time of {}: {e}", path.display())
+                }
+            }
+        }
+    }
+
+    /// Create the mapping of a map which is added to an already started bridge,
+    /// using the same code path as [`Bridge::start()`].
+    ///
+    /// If the mapping can't be created, the file is removed again if we created
+    /// it, and the map isn't added to the bridge.
+    pub fn start_map(&mut self, map: MapSpec) -> Result<MapStatus> {
+        if self.maps.iter().any(|existing| existing.name == map.name) {
+            bail!("The map {} is already managed by the bridge", map.name);
+        }
+
+        let mut created = Vec::new();
+
+        let (mut mapping, status) = match self.create_mapping(&map, &mut created) {
+            Ok(result) => result,
+            Err(e) => {
+                self.roll_back(created);
+                return Err(e);
+            }
+        };
+
+        if !self.keep_files && !self.attached.contains(&map.name) {
+            mapping.own_file(self.file_path(&map));
+        }
+
+        self.statuses.push((map.name.clone(), status));
+        self.maps.push(map);
+        self.mappings.push(mapping);
+
+        Ok(status)
+    }
+
+    /// Close the mapping of the map with the given name, and unlink its
+    /// `/dev/shm` backed file and its aliases, using the same code path as
+    /// [`Bridge::cleanup()`].
+    ///
+    /// The file of an attached map is left alone. Aliases which couldn't be
+    /// removed are part of the returned summary, they don't keep the file of
+    /// the map around.
+    pub fn stop_map(&mut self, name: &str) -> Result<CleanUpSummary> {
+        let index = self
+            .maps
+            .iter()
+            .position(|map| map.name == name)
+            .ok_or_else(|| anyhow!("The map {name} isn't managed by the bridge"))?;
+
+        let mut mapping = self.mappings.remove(index);
+        mapping.release_file();
+        drop(mapping);
+
+        let attached = self.attached.contains(&self.maps[index].name);
+        let (map, aliases) = self.forget_map(index);
+        let owned = if attached { Vec::new() } else { vec![map] };
+
+        // The map isn't tracked anymore, so its file is removed even if one of the
+        // aliases can't be.
+        let mut summary = clean_up_summary(&self.dir, &self.prefix, &owned, self.retry_policy);
+        summary.failed.extend(clean_up_aliases(&self.dir, &aliases));
+
+        Ok(summary)
+    }
+
+    /// Stop managing the map at the given index, whose mapping was already
+    /// removed, the files of the map and its aliases are left alone.
+    ///
+    /// Returns the map together with its aliases.
+    fn forget_map(&mut self, index: usize) -> (MapSpec, Vec<(String, String)>) {
+        let map = self.maps.remove(index);
+        let name = &map.name;
+
+        self.statuses.retain(|(status_name, _)| status_name != name);
+        self.attached.retain(|attached| attached != name);
+
+        let (aliases, remaining) = std::mem::take(&mut self.aliases)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(alias_name, _)| alias_name == name);
+        self.aliases = remaining;
+        self.links.retain(|link| !aliases.iter().any(|(_, alias)| *link == self.dir.join(alias)));
+
+        (map, aliases)
+    }
+
+    /// Update the status of the map with the given name.
+    fn set_status(&mut self, name: &str, status: MapStatus) {
+        match self.statuses.iter_mut().find(|(status_name, _)| status_name == name) {
+            Some((_, existing)) => *existing = status,
+            None => self.statuses.push((name.to_owned(), status)),
+        }
+    }
+
+    /// Replace the `/dev/shm` backed file of a map with a new one and create a
+    /// new mapping for it, the aliases of the map are pointed to the new file.
+    ///
+    /// If the mapping can't be created, the new file is removed again.
+    fn recreate_mapping(&self, map: &MapSpec) -> Result<(FileMapping, MapStatus)> {
+        let path = self.file_path(map);
+        let attached = self.attached.contains(&map.name);
+
+        // The files of attached maps belong to a Linux process, which might have
+        // replaced the file, so we only attach to the new file.
+        if !attached {
+            match remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(permission_hint(e, &path)),
+            }
+        }
+
+        let mut created = Vec::new();
+
+        let (mut mapping, status) = match self.create_mapping(map, &mut created) {
+            Ok(result) => result,
+            Err(e) => {
+                self.roll_back(created);
+                return Err(e);
+            }
+        };
+
+        if !self.keep_files && !attached {
+            mapping.own_file(&path);
+        }
+
+        for (_, alias) in self.aliases.iter().filter(|(name, _)| name == &map.name) {
+            let link = self.dir.join(alias);
+            let _ = remove_file(&link);
+
+            match hard_link(&path, &link) {
+                Ok(()) => info!("Pointed the alias {alias} to the new file of {}", map.name),
+                Err(e) => warn!("Could not recreate the alias {alias} of {}: {e}", map.name),
+            }
+        }
+
+        Ok((mapping, status))
+    }
+
+    /// Create the hard links for the aliases of the maps.
+    ///
+    /// If one of the links can't be created, the links created so far are
+    /// removed again.
+    fn create_aliases(&self) -> Result<Vec<PathBuf>> {
+        let mut links = Vec::with_capacity(self.aliases.len());
+
+        for (name, alias) in &self.aliases {
+            let result = self
+                .maps
+                .iter()
+                .find(|map| &map.name == name)
+                .ok_or_else(|| anyhow!("The map {name} of the alias {alias} doesn't exist"))
+                .and_then(|map| {
+                    let target = self.file_path(map);
+                    let link = self.dir.join(alias);
+
+                    if link.exists() {
+                        info!("Replacing the existing alias {alias} at {}", link.display());
+                        remove_file(&link).map_err(|e| permission_hint(e, &link))?;
+                    }
+
+                    hard_link(&target, &link)
+                        .map_err(|e| permission_hint(e, &link))
+                        .with_context(|| format!("Could not create the alias {alias} of {name}"))?;
+
+                    info!("Created the alias {alias} of the mapping {name} at {}", link.display());
+
+                    Ok(link)
+                });
+
+            match result {
+                Ok(link) => links.push(link),
+                Err(e) => {
+                    for link in links {
+                        let _ = remove_file(link);
+                    }
+
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(links)
+    }
+
+    /// Remove the `/dev/shm` backed files of the given maps which we created.
+    fn roll_back(&self, mut created: Vec<MapSpec>) {
+        // The file of the failed map might not have been created at all.
+        created.retain(|map| self.file_path(map).exists());
+
+        if let Err(cleanup_error) = clean_up(&self.dir, &self.prefix, &created, self.retry_policy) {
+            warn!("The rollback failed: {cleanup_error:#}");
+        }
+    }
+
+    /// Reopen the `/dev/shm` backed file of a map, check its size and read its
+    /// first and last byte to ensure the memory is accessible.
+    fn verify_mapping(&self, map: &MapSpec) -> Result<()> {
+        let MapSpec { name, size, .. } = map;
+        let path = self.file_path(map);
+
+        let file = File::open(&path)
+            .with_context(|| format!("Could not reopen the /dev/shm backed file of {name}"))?;
+        let len = file
+            .metadata()
+            .with_context(|| format!("Couldn't query the size of {}", path.display()))?
+            .len();
+
+        if len < *size as u64 {
+            return Err(anyhow!(
+                "The /dev/shm backed file of {name} only has {len} bytes instead of {size}"
+            ));
+        }
+
+        let mapping = FileMapping::open_read_only(&file)
+            .with_context(|| format!("Could not map the /dev/shm backed file of {name}"))?;
+        let view = mapping.view()?;
+
+        // Safety: The view is valid for `len()` bytes as long as it is alive, and
+        // `open_read_only()` refuses to map empty files.
+        let (first, last) = unsafe {
+            (view.as_ptr().read_volatile(), view.as_ptr().add(view.len() - 1).read_volatile())
+        };
+
+        debug!("The first byte of {name} is {first:#04x}, the last one {last:#04x}");
+        info!("Verified the mapping {name}, its file has {len} bytes and is readable");
+
+        Ok(())
+    }
+
+    /// Replace the content of the map with the given name, the content is
+    /// truncated or padded with zeros to the size of the map.
+    ///
+    /// The map needs to be created using [`Bridge::start()`] first, the number
+    /// of copied bytes is returned. The mapping is flushed afterwards if
+    /// [`Bridge::sync()`] is set.
+    pub fn write_map(&self, name: &str, content: &[u8]) -> Result<usize> {
+        let mapping = self
+            .maps
+            .iter()
+            .position(|map| map.name == name)
+            .and_then(|index| self.mappings.get(index))
+            .ok_or_else(|| anyhow!("The map {name} hasn't been created"))?;
+
+        mapping.zero().with_context(|| format!("Error zeroing the file mapping for {name}"))?;
+        let copied =
+            mapping.write(content).with_context(|| format!("Error writing to the map {name}"))?;
+
+        if self.sync {
+            mapping
+                .flush()
+                .with_context(|| format!("Error flushing the file mapping for {name}"))?;
+        }
+
+        Ok(copied)
+    }
+
+    /// Close the file mappings and unlink their `/dev/shm` backed files and
+    /// aliases.
+    ///
+    /// A failure to remove one of the files or aliases doesn't stop us from
+    /// removing the rest, the returned summary contains which of the files were
+    /// removed and which couldn't be, see [`clean_up_summary()`] and
+    /// [`clean_up_aliases()`].
+    pub fn cleanup(&mut self) -> CleanUpSummary {
+        // The files are removed explicitly, so failures get reported.
+        for mapping in &mut self.mappings {
+            mapping.release_file();
+        }
+
+        self.mappings.clear();
+
+        // The files of attached maps aren't ours to remove.
+        let owned: Vec<_> =
+            self.maps.iter().filter(|map| !self.attached.contains(&map.name)).cloned().collect();
+
+        let mut summary = clean_up_summary(&self.dir, &self.prefix, &owned, self.retry_policy);
+
+        if !self.links.is_empty() {
+            self.links.clear();
+            summary.failed.extend(clean_up_aliases(&self.dir, &self.aliases));
+        }
+
+        summary
+    }
+
+    /// The number of bytes allocated for the given map, including the padding.
+    ///
+    /// Attached maps have the size of their file, without any padding.
+    fn allocated_size(&self, map: &MapSpec) -> Result<usize> {
+        if self.attached.contains(&map.name) {
+            return Ok(map.size);
+        }
+
+        map.size.checked_add(self.padding).ok_or_else(|| {
+            anyhow!("The size of the map {} including the padding is too large", map.name)
+        })
+    }
+
+    /// Make the views of the mapping use the base address of the map, if it has
+    /// one, and check that a view can be mapped at it.
+    fn apply_base_address(&self, map: &MapSpec, mapping: &mut FileMapping) -> Result<()> {
+        let name = &map.name;
+        let base_address = self
+            .base_addresses
+            .iter()
+            .find(|(map_name, _)| map_name == name)
+            .map(|(_, address)| *address)
+            .or(map.base_address);
+
+        let Some(address) = base_address else {
+            return Ok(());
+        };
+
+        let granularity = allocation_granularity();
+
+        if address % granularity != 0 {
+            bail!(
+                "The base address {address:#x} of the map {name} isn't a multiple of the \
+                 allocation granularity, {granularity:#x}"
+            );
+        }
+
+        mapping.set_base_address(Some(address));
+        mapping
+            .view()
+            .with_context(|| format!("The system can't map the map {name} at {address:#x}"))?;
+
+        debug!("Mapping the views of {name} at the base address {address:#x}");
+
+        Ok(())
+    }
+
+    /// Check that none of the existing `/dev/shm` backed files has a different
+    /// size than its map, see [`Bridge::strict_sizes()`].
+    fn check_sizes(&self) -> Result<()> {
+        let mut mismatches = Vec::new();
+
+        for map in self.maps.iter().filter(|map| !self.attached.contains(&map.name)) {
+            let path = self.file_path(map);
+            let allocated = self.allocated_size(map)?;
+
+            if let Some(existing) = existing_size(&path)? {
+                if file_action(Some(existing), allocated, round_to_page_size(allocated), false)
+                    .is_none()
+                {
+                    mismatches.push(SizeMismatch {
+                        name: map.name.clone(),
+                        path,
+                        existing,
+                        requested: allocated,
+                    });
+                }
+            }
+        }
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::StrictSizeMismatch { mismatches }.into())
+        }
+    }
+
+    /// Create the file mapping for a single map.
+    ///
+    /// If the `/dev/shm` backed file of the map is going to be created by us,
+    /// the map is added to the `created` list before the file is created.
+    fn create_mapping(
+        &self,
+        map: &MapSpec,
+        created: &mut Vec<MapSpec>,
+    ) -> Result<(FileMapping, MapStatus)> {
+        let MapSpec { name, size, .. } = map;
+        let path = self.file_path(map);
+
+        let protection = if self.read_only.contains(name) || map.access == Access::ReadOnly {
+            Protection::ReadOnly
+        } else {
+            Protection::ReadWrite
+        };
+
+        if self.attached.contains(name) {
+            let (mut mapping, size) =
+                attach_file_mapping(&path, name, protection, self.retry_policy)
+                    .with_context(|| format!("Error attaching a file mapping to {name}"))?;
+            self.apply_base_address(map, &mut mapping)?;
+
+            info!(
+                "Attached a mapping for {name} to the existing file {} with size {size}",
+                path.display()
+            );
+            output::emit(&Event::MappingCreated {
+                name,
+                size,
+                path: &path,
+                status: MapStatus::Attached,
+            });
+
+            return Ok((mapping, MapStatus::Attached));
+        }
+
+        let allocated = self.allocated_size(map)?;
+        let existing = existing_size(&path)?;
+
+        let Some(action) =
+            file_action(existing, allocated, round_to_page_size(allocated), self.force)
+        else {
+            return Err(Error::SizeMismatch {
+                name: name.clone(),
+                path,
+                existing: existing.unwrap_or_default(),
+                requested: allocated,
+            }
+            .into());
+        };
+
+        // The content needs to be read before the old file is gone.
+        let migrated = if action == FileAction::Recreate && self.migrate {
+            if protection == Protection::ReadOnly {
+                bail!("The content of the read-only map {name} can't be migrated");
+            }
+
+            let mut content = std::fs::read(&path).with_context(|| {
+                format!("Could not read the existing file of the map {name} to migrate it")
+            })?;
+            content.truncate(*size);
+
+            Some(content)
+        } else {
+            None
+        };
+
+        // Files left behind by a previous run are removed, so we're sure to get a
+        // fresh file with the correct size.
+        if action == FileAction::Recreate {
+            clean_up(&self.dir, &self.prefix, std::slice::from_ref(map), self.retry_policy)?;
+        }
+
+        if action == FileAction::Reuse {
+            check_owner(name, &path, self.attributes)?;
+        }
+
+        let newly_created = action != FileAction::Reuse;
+
+        if newly_created {
+            created.push(map.clone());
+        }
+
+        let (mut mapping, status) = create_file_mapping(
+            &path,
+            name,
+            allocated,
+            protection,
+            self.attributes,
+            self.retry_policy,
+        )
+        .with_context(|| format!("Error creating a file mapping for {name}"))?;
+        self.apply_base_address(map, &mut mapping)?;
+
+        // The old file was removed above, so `create_file_mapping()` created a new one.
+        let status = match action {
+            FileAction::Recreate => MapStatus::Recreated,
+            _ => status,
+        };
+
+        // Reused files might contain stale data from a previous run, so we leave them
+        // alone, the simulator might still be using them. Newly created files are
+        // empty, so read-only mappings don't need to be zeroed.
+        let initialized =
+            newly_created && !mapping.is_read_only() && (self.zero || migrated.is_some());
+
+        if initialized {
+            mapping.zero().with_context(|| format!("Error zeroing the file mapping for {name}"))?;
+        }
+
+        if let Some(content) = migrated {
+            let copied = mapping
+                .write(&content)
+                .with_context(|| format!("Error migrating the content of the map {name}"))?;
+
+            info!("Migrated {copied} bytes of the previous content of the map {name}");
+        }
+
+        if initialized && self.sync {
+            mapping
+                .flush()
+                .with_context(|| format!("Error flushing the file mapping for {name}"))?;
+            debug!("Flushed the file mapping for {name}");
+        }
+
+        if let Some(mode) = self.mode.filter(|_| newly_created) {
+            unix::chmod(&path, mode)?;
+        }
+
+        if let Some(gid) = self.gid.filter(|_| newly_created) {
+            // Changing the group requires the user to be a member of the group, this
+            // isn't fatal, the file is still usable by its owner.
+            if let Err(e) = unix::chgrp(&path, gid) {
+                warn!("{e:#}");
+            }
+        }
+
+        let padding = match self.padding {
+            0 => String::new(),
+            padding => format!(" and {padding} bytes of padding"),
+        };
+
+        if newly_created {
+            let verb = if status == MapStatus::Recreated { "Recreated" } else { "Created" };
+
+            info!(
+                "{verb} a tmpfs backed mapping for {name} at {} with size {size}{padding}",
+                path.display()
+            );
+        } else {
+            let level = if self.quiet_on_reuse { Level::Debug } else { Level::Info };
+
+            log!(
+                level,
+                "Reused the existing tmpfs backed mapping for {name} at {} with size \
+                 {size}{padding}",
+                path.display()
+            );
+        }
+
+        output::emit(&Event::MappingCreated { name, size: *size, path: &path, status });
+
+        Ok((mapping, status))
+    }
+}
+
+impl Drop for Bridge {
+    fn drop(&mut self) {
+        // The mappings remove their own files, but the aliases are ours.
+        if !self.keep_files {
+            for link in &self.links {
+                // There's not much we can do if an error happens here, so let's ignore it.
+                let _ = remove_file(link);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_and_mapping_sizes_agree() {
+        let page_size = round_to_page_size(1);
+
+        assert!(sizes_agree(page_size as u64, 2048.min(page_size)));
+        assert!(sizes_agree(page_size as u64, page_size));
+        assert!(sizes_agree(2 * page_size as u64, page_size + 1));
+        assert!(!sizes_agree(page_size as u64, page_size + 1));
+        assert!(!sizes_agree(2048, 2048));
+    }
+
+    #[test]
+    fn missing_files_are_created() {
+        assert_eq!(file_action(None, 2048, 4096, false), Some(FileAction::Create));
+        assert_eq!(file_action(None, 2048, 4096, true), Some(FileAction::Create));
+    }
+
+    #[test]
+    fn files_with_the_same_size_are_reused() {
+        assert_eq!(file_action(Some(2048), 2048, 4096, false), Some(FileAction::Reuse));
+        assert_eq!(file_action(Some(4096), 2048, 4096, false), Some(FileAction::Reuse));
+    }
+
+    #[test]
+    fn files_with_a_different_size_are_rejected() {
+        assert_eq!(file_action(Some(1024), 2048, 4096, false), None);
+        assert_eq!(file_action(Some(8192), 2048, 4096, false), None);
+        assert_eq!(file_action(Some(0), 2048, 4096, false), None);
+    }
+
+    #[test]
+    fn attributes() {
+        assert_eq!(parse_attributes("0x100"), Ok(DEFAULT_FILE_ATTRIBUTES));
+        assert_eq!(parse_attributes("2100"), Ok(0x2100));
+        assert!(parse_attributes("0x10").is_err());
+        assert!(parse_attributes("temporary").is_err());
+    }
+
+    #[test]
+    fn forced_files_are_recreated() {
+        assert_eq!(file_action(Some(1024), 2048, 4096, true), Some(FileAction::Recreate));
+        assert_eq!(file_action(Some(2048), 2048, 4096, true), Some(FileAction::Recreate));
+    }
+}
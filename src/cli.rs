@@ -0,0 +1,738 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::{path::PathBuf, time::Duration};
+
+use clap::{ArgAction, Args, Parser, Subcommand};
+use shm_bridge::{
+    bridge::parse_attributes,
+    config::ConfigFormat,
+    header::{parse_size_from, Endianness, SizeFrom, SizeWidth},
+    map_spec::{parse_address, parse_map_arg, MapArg},
+    output::OutputFormat,
+    presets::Preset,
+    retry::RetryPolicy,
+    size::{parse_size, SizeArg},
+    unix::parse_mode,
+};
+
+use crate::logging::ColorChoice;
+
+const LONG_ABOUT: &str = "Shared Memory Bridge facilitates sharing memory between Windows\n\
+                          applications running under Wine/Proton and Linux, offering a seamless\n\
+                          way to access and manipulate named shared memory spaces across these\n\
+                          platforms. It's particularly useful in gaming and simulations, allowing\n\
+                          Linux applications to directly read data from Windows applications.\n\n\
+                          Example Usage:\n\n\
+                          To launch the bridge and view command line options, use the following \
+                          command:\n    \
+                              protontricks-launch --appid APPID shm-bridge.exe\n\n\
+                          This will display help output and available options for `shm-bridge`,\n\
+                          guiding you through the necessary steps to set up and run the bridge\n\
+                          within your specific environment.";
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = LONG_ABOUT, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The arguments used if no subcommand is given.
+    #[command(flatten)]
+    legacy: LegacyArgs,
+
+    #[command(flatten)]
+    global: GlobalArgs,
+
+    /// Print the version and the supported features as JSON.
+    #[arg(long, exclusive = true)]
+    pub version_json: bool,
+}
+
+impl Cli {
+    /// Split the parsed arguments into the global arguments and the subcommand
+    /// that should be run.
+    ///
+    /// If no subcommand was given, the `create` subcommand is run, unless one
+    /// of the flags that predate the subcommands selects a different one.
+    pub fn into_parts(self) -> (GlobalArgs, Command) {
+        let command = self.command.unwrap_or_else(|| self.legacy.into_command());
+
+        (self.global, command)
+    }
+}
+
+// The command is parsed once and lives until we exit, the size doesn't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+pub enum Command {
+    /// Create the mappings and keep them alive until CTRL-C is pressed. This is
+    /// the default if no subcommand is given.
+    Create(CreateArgs),
+
+    /// Remove the `/dev/shm` files of the given maps. This is useful if the
+    /// bridge was killed and couldn't clean up after itself.
+    Clean(CleanArgs),
+
+    /// Print a hex dump of an existing `/dev/shm` backed file, or write its
+    /// content to a file.
+    Dump(DumpArgs),
+
+    /// List the files in the shm directory together with their sizes.
+    List(ListArgs),
+
+    /// Check that the `/dev/shm` backed files of the given maps exist and have
+    /// the expected sizes, without creating or modifying anything.
+    Check(CheckArgs),
+
+    /// Print the page size and the allocation granularity of the system, and
+    /// how the sizes of the given maps are rounded to them.
+    Info(InfoArgs),
+
+    /// Create, zero-fill, read back, and remove a temporary map, timing each
+    /// phase. This helps to diagnose a slow shm directory.
+    Selftest(SelftestArgs),
+}
+
+/// Arguments which are accepted by all the subcommands.
+#[derive(Args)]
+pub struct GlobalArgs {
+    /// The directory the `/dev/shm` backed files should be placed in, by
+    /// default a tmpfs is searched for in `/proc/mounts`. With `auto` the
+    /// tmpfs with the most free space is selected.
+    #[arg(long, global = true)]
+    pub shm_dir: Option<PathBuf>,
+
+    /// A prefix which is prepended to the names of the `/dev/shm` backed files,
+    /// the Win32 names of the maps stay the same. This lets multiple bridge
+    /// instances, whose maps use the same names, share a shm directory.
+    #[arg(long, global = true, value_parser = parse_prefix)]
+    pub prefix: Option<String>,
+
+    /// Interpret the K, M, and G suffixes of the map sizes and the padding as
+    /// powers of 1000, i.e. `1M` is 1000000 bytes, instead of powers of 1024.
+    /// This applies to the sizes in config files and in the environment as
+    /// well, other sizes like `--max-size` keep using binary multipliers.
+    #[arg(long, global = true)]
+    pub si_sizes: bool,
+
+    /// How often the creation of a mapping, whose name is already taken, and
+    /// the removal of a `/dev/shm` backed file are retried.
+    #[arg(long, global = true, default_value_t = RetryPolicy::DEFAULT_RETRIES)]
+    pub retries: u32,
+
+    /// How long to wait before the first retry in milliseconds, the delay is
+    /// doubled after every retry.
+    #[arg(long, global = true, default_value_t = RetryPolicy::DEFAULT_DELAY_MS)]
+    pub retry_delay: u64,
+
+    /// Print the tmpfs and ramfs mounts that were considered for the shm
+    /// directory, their free space, and which one was selected.
+    #[arg(long, global = true)]
+    pub verbose_mounts: bool,
+
+    /// Print more detailed status messages, can be given multiple times.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Only print warnings and errors.
+    #[arg(short, long, conflicts_with = "verbose", global = true)]
+    pub quiet: bool,
+
+    /// The format of the output printed to stdout. The JSON format prints a
+    /// JSON object for every created or removed mapping and for errors.
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub output: OutputFormat,
+
+    /// Color the warnings, errors, and success messages. By default the
+    /// messages are colored if stderr is a terminal. Colors are never used with
+    /// `--quiet` or `--output json`.
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub color: ColorChoice,
+}
+
+impl GlobalArgs {
+    /// Get the prefix of the `/dev/shm` backed files, which is empty unless
+    /// `--prefix` was given.
+    pub fn file_prefix(&self) -> &str {
+        self.prefix.as_deref().unwrap_or_default()
+    }
+
+    /// Get the retry policy described by `--retries` and `--retry-delay`.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::new(self.retries, Duration::from_millis(self.retry_delay))
+    }
+}
+
+/// Arguments selecting the maps a subcommand works on.
+#[derive(Args)]
+pub struct MapArgs {
+    /// The name of a shared memory mapping, can be given multiple times or as
+    /// a comma separated list. The size can be given inline using the
+    /// `name:size` syntax. If no mapping is given, the mappings of the `acc`
+    /// preset are used.
+    #[arg(short, long, value_parser = parse_map_arg, value_delimiter = ',')]
+    pub map: Vec<MapArg>,
+
+    /// The size of the shared memory mapping, can be given multiple times or
+    /// as a comma separated list. One size needs to be given for each map that
+    /// doesn't use the `name:size` syntax. Bare numbers are interpreted as
+    /// bytes, the K, M, and G suffixes use binary multipliers, e.g. `32M` is
+    /// 33554432 bytes.
+    #[arg(short, long, value_parser = SizeArg::parse, value_delimiter = ',')]
+    pub size: Vec<SizeArg>,
+
+    /// The size every `--map` argument that doesn't use the `name:size` syntax
+    /// should have. This can't be combined with `--size`.
+    #[arg(long, value_parser = SizeArg::parse, conflicts_with = "size")]
+    pub size_all: Option<SizeArg>,
+
+    /// A TOML or JSON config file containing a list of maps. Maps given on the
+    /// command line take precedence over maps with the same name found in the
+    /// config file.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Read the maps from stdin until EOF, one map per line in the `name size`
+    /// or the `name:size` form. Empty lines and lines starting with `#` are
+    /// skipped. The maps are treated like maps from a config file.
+    #[arg(long, conflicts_with = "config")]
+    pub from_stdin: bool,
+
+    /// The format of the config file, by default files with a `.json`
+    /// extension are parsed as JSON and every other file as TOML.
+    #[arg(long, value_enum, requires = "config")]
+    pub config_format: Option<ConfigFormat>,
+
+    /// Use the mappings a known simulator uses. Additional maps can be given
+    /// using `--map` or `--config`.
+    #[arg(short, long)]
+    pub preset: Option<Preset>,
+
+    /// If a map name was given multiple times, keep the last definition instead
+    /// of returning an error.
+    #[arg(long)]
+    pub dedupe: bool,
+
+    /// The number of extra bytes which are allocated after the end of every
+    /// map, reads past the requested size then land in zeroed memory.
+    #[arg(long, value_parser = SizeArg::parse, default_value = "0")]
+    pub padding: SizeArg,
+}
+
+/// The arguments of the `create` subcommand.
+#[derive(Args)]
+pub struct CreateArgs {
+    #[command(flatten)]
+    pub maps: MapArgs,
+
+    /// Print the files that would be created, or removed when cleaning up,
+    /// instead of touching them. Every file is printed on a separate line,
+    /// containing the action, the path, and in the case of a creation the
+    /// size in bytes, separated by spaces.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Remove the `/dev/shm` backed files of the maps if they already exist,
+    /// instead of reusing them.
+    #[arg(short, long)]
+    pub force: bool,
+
+    /// Copy the content of existing `/dev/shm` backed files which `--force`
+    /// recreates into the new files. If the size of a map changed, the content
+    /// is truncated or the rest of the map is filled with zeros.
+    #[arg(long, requires = "force")]
+    pub migrate: bool,
+
+    /// The file attributes, as a hexadecimal number, the `/dev/shm` backed
+    /// files are created with, by default `0x100`
+    /// (FILE_ATTRIBUTE_TEMPORARY). Meant for debugging how Wine treats the
+    /// files, other values are unsupported.
+    #[arg(long, value_parser = parse_attributes, value_name = "HEX")]
+    pub attributes: Option<u32>,
+
+    /// Flush the mappings after they were zeroed, migrated, or initialized
+    /// using `--init-from`, so the content is written back to the files before
+    /// the simulator attaches. This does nothing for maps which weren't
+    /// written to.
+    #[arg(long)]
+    pub sync: bool,
+
+    /// Refuse to start if the `/dev/shm` backed file of any map already exists
+    /// with a different size, instead of reusing or recreating the files. All
+    /// the mismatched maps are listed before exiting.
+    #[arg(long, conflicts_with = "force")]
+    pub strict_sizes: bool,
+
+    /// Don't print a status message for every existing `/dev/shm` backed file
+    /// which gets reused, the message is still printed with `--verbose`.
+    /// Warnings about the reused files are printed regardless.
+    #[arg(long)]
+    pub quiet_on_reuse: bool,
+
+    /// Don't overwrite the content of newly created maps with zeros.
+    #[arg(long)]
+    pub no_zero: bool,
+
+    /// Initialize the content of a map with the content of a file, given as
+    /// `name=path`, can be given multiple times. The content is truncated, or
+    /// padded with zeros, to the size of the map.
+    #[arg(long, value_name = "MAPNAME=PATH", value_parser = parse_init_from)]
+    pub init_from: Vec<(String, PathBuf)>,
+
+    /// Make the `/dev/shm` backed file of a map available under another file
+    /// name as well, given as `name=alias`, can be given multiple times. The
+    /// alias is a hard link to the file of the map.
+    #[arg(long, value_name = "MAPNAME=ALIAS", value_parser = parse_alias)]
+    pub alias: Vec<(String, String)>,
+
+    /// Create the mapping of the given map with read-only protection, can be
+    /// given multiple times. This is meant for maps another process writes to
+    /// through the `/dev/shm` backed file, while Windows applications only read
+    /// them.
+    #[arg(long, value_name = "MAPNAME")]
+    pub read_only: Vec<String>,
+
+    /// Map the existing `/dev/shm` backed file of the given map, which a Linux
+    /// process created, as is, can be given multiple times. The map gets the
+    /// size of the file, which isn't created, resized, or removed by the
+    /// bridge.
+    #[arg(long, value_name = "MAPNAME", value_delimiter = ',')]
+    pub attach: Vec<String>,
+
+    /// Map the views of the given map at a fixed address, given as
+    /// `name=address` with a hexadecimal address, can be given multiple times.
+    /// This is unsupported and only meant for compatibility experiments, it
+    /// only affects the views the bridge maps itself.
+    #[arg(long, value_name = "MAPNAME=ADDRESS", value_parser = parse_base_address)]
+    pub base_address: Vec<(String, usize)>,
+
+    /// Create a map whose size is read from another map, given as
+    /// `name=source:offset`, can be given multiple times. The source map needs
+    /// to exist already, the size is read at the given offset, in bytes, from
+    /// the named mapping or, if it doesn't exist, from the `/dev/shm` backed
+    /// file of the source map.
+    #[arg(long, value_name = "MAPNAME=SOURCE:OFFSET", value_parser = parse_size_from)]
+    pub size_from: Vec<SizeFrom>,
+
+    /// The width of the sizes read by `--size-from`.
+    #[arg(long, value_enum, default_value_t, requires = "size_from")]
+    pub size_width: SizeWidth,
+
+    /// The byte order of the sizes read by `--size-from`.
+    #[arg(long, value_enum, default_value_t, requires = "size_from")]
+    pub size_endianness: Endianness,
+
+    /// A command which is run by the Linux shell once all the mappings were
+    /// created. The bridge keeps running while the command does, the Linux
+    /// path of the shm directory is passed in the `SHM_BRIDGE_DIR` environment
+    /// variable.
+    #[arg(long, value_name = "COMMAND")]
+    pub on_ready: Option<String>,
+
+    /// A command which is run by the Linux shell when the bridge shuts down,
+    /// before the mappings are removed. The bridge waits for the command to
+    /// finish.
+    #[arg(long, value_name = "COMMAND", conflicts_with = "no_park")]
+    pub on_cleanup: Option<String>,
+
+    /// Write the process ID and the managed maps to the given file, the file
+    /// can be used with `clean --from-pid-file` to clean up if the bridge was
+    /// killed.
+    #[arg(long)]
+    pub pid_file: Option<PathBuf>,
+
+    /// Keep a JSON file describing the managed maps, their sizes, paths, and
+    /// creation times, up to date while the bridge is running. The file is
+    /// placed in the shm directory as `shm-bridge.status` if no path is given,
+    /// and removed once the bridge shuts down.
+    #[arg(long, value_name = "PATH", num_args = 0..=1, conflicts_with = "no_park")]
+    pub status_file: Option<Option<PathBuf>>,
+
+    /// The permission bits, in octal, newly created `/dev/shm` backed files
+    /// should have, i.e. `0660`. Reused files keep their permissions.
+    #[arg(long, value_parser = parse_mode)]
+    pub mode: Option<u32>,
+
+    /// The group, either a name or a numeric group ID, newly created
+    /// `/dev/shm` backed files should belong to.
+    #[arg(long)]
+    pub group: Option<String>,
+
+    /// Don't remove the `/dev/shm` backed files when shutting down, they can be
+    /// removed later on using the `clean` subcommand.
+    #[arg(long)]
+    pub keep_on_exit: bool,
+
+    /// Instead of running until CTRL-C is pressed, wait until a process maps
+    /// one of the `/dev/shm` backed files and shut down once no process has
+    /// them mapped anymore.
+    #[arg(long)]
+    pub wait_for_consumer: bool,
+
+    /// The number of seconds `--wait-for-consumer` waits, after the last
+    /// consumer detached, before shutting down.
+    #[arg(long, default_value_t = 5.0, requires = "wait_for_consumer")]
+    pub grace_period: f64,
+
+    /// Shut down after the given number of seconds, instead of running until
+    /// CTRL-C is pressed.
+    #[arg(long, value_name = "SECONDS", conflicts_with = "wait_for_consumer")]
+    pub timeout: Option<f64>,
+
+    /// If creating the mappings or running the bridge fails, clean up and try
+    /// again after a delay instead of exiting. CTRL-C still stops the bridge.
+    #[arg(long, conflicts_with = "no_park")]
+    pub restart_on_crash: bool,
+
+    /// Keep creating the remaining maps if the mapping of one map can't be
+    /// created, and run with the maps which could be created. The maps which
+    /// failed are listed once all the mappings were created, and the bridge
+    /// exits with an error once it's stopped. Without it the bridge fails
+    /// fast, it stops and removes the files created so far as soon as the
+    /// mapping of one map can't be created.
+    #[arg(long, conflicts_with = "restart_on_crash")]
+    pub best_effort: bool,
+
+    /// The number of times `--restart-on-crash` restarts the bridge before
+    /// giving up.
+    #[arg(long, default_value_t = 5, requires = "restart_on_crash")]
+    pub max_restarts: u32,
+
+    /// Warn if, after the given number of seconds, no other process has mapped
+    /// a `/dev/shm` backed file or it only contains zeros. This usually means
+    /// the simulator created its own mapping instead of using ours.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        default_missing_value = "30",
+        conflicts_with = "no_park"
+    )]
+    pub watch_adoption: Option<f64>,
+
+    /// Check every given number of seconds, 5 if no number is given, that the
+    /// `/dev/shm` backed files still exist with the expected size, and recreate
+    /// the mappings whose files were removed or resized by another process.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        default_missing_value = "5",
+        conflicts_with_all = ["no_park", "wait_for_consumer"]
+    )]
+    pub watch_maps: Option<f64>,
+
+    /// Update the modification time of the `/dev/shm` backed files every given
+    /// number of seconds, so tools removing stale files can tell that the
+    /// bridge is still running.
+    #[arg(long, value_name = "SECONDS", conflicts_with_all = ["no_park", "wait_for_consumer"])]
+    pub touch_interval: Option<f64>,
+
+    /// Reload the `--config` file once it's modified, i.e. using `touch`,
+    /// creating the maps which were added and removing the maps which aren't
+    /// listed anymore. Wine doesn't forward `SIGHUP` to Windows applications,
+    /// so the modification time of the file is checked every second instead.
+    #[arg(long, requires = "config", conflicts_with_all = ["no_park", "wait_for_consumer"])]
+    pub reload_config: bool,
+
+    /// After creating the mappings, reopen every `/dev/shm` backed file, check
+    /// its size and that its content is readable. The created files are
+    /// removed again if the verification fails.
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Exit right after the mappings were created, instead of running until
+    /// CTRL-C is pressed. The `/dev/shm` backed files are kept, they need to be
+    /// removed later on using the `clean` subcommand.
+    #[arg(long, conflicts_with_all = ["timeout", "wait_for_consumer", "pid_file"])]
+    pub no_park: bool,
+
+    /// Instead of creating the mappings, print how much space every map takes
+    /// up, the running total, and the available space of the shm directory.
+    /// The first map which doesn't fit anymore is marked.
+    #[arg(long)]
+    pub space_report: bool,
+
+    /// Create the mappings even if the directory they are placed in isn't on a
+    /// tmpfs or ramfs filesystem.
+    #[arg(long)]
+    pub allow_non_tmpfs: bool,
+
+    /// Place the `/dev/shm` backed files on a mounted hugetlbfs, so the maps
+    /// are backed by huge pages. The maps are rounded up to a multiple of the
+    /// huge page size, if no hugetlbfs is mounted regular pages are used. This
+    /// can't be combined with `--shm-dir`.
+    #[arg(long)]
+    pub hugepages: bool,
+
+    /// The largest size a single map may have, larger maps are refused unless
+    /// `--allow-huge` is given.
+    #[arg(long, value_parser = parse_size, default_value = "1G")]
+    pub max_size: usize,
+
+    /// Create maps which are larger than `--max-size`.
+    #[arg(long)]
+    pub allow_huge: bool,
+}
+
+/// The arguments of the `clean` subcommand.
+#[derive(Args)]
+pub struct CleanArgs {
+    #[command(flatten)]
+    pub maps: MapArgs,
+
+    /// Print the files that would be removed instead of removing them. Every
+    /// file is printed on a separate line, containing the action and the path
+    /// separated by a space.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Clean up the maps recorded in the given PID file, the maps and the shm
+    /// directory don't need to be given again.
+    #[arg(
+        long,
+        conflicts_with_all = ["map", "size", "size_all", "config", "preset", "shm_dir"]
+    )]
+    pub from_pid_file: Option<PathBuf>,
+
+    /// Remove the files matching the `prune` patterns of the config file as
+    /// well, even though they don't belong to any of the given maps. With
+    /// `--dry-run` every line contains the reason, `listed` or `orphan`, as
+    /// well.
+    #[arg(long, requires = "config")]
+    pub prune: bool,
+
+    /// Remove the aliases, given as `name=alias`, which were created using
+    /// the `--alias` argument of the `create` subcommand as well.
+    #[arg(long, value_name = "MAPNAME=ALIAS", value_parser = parse_alias)]
+    pub alias: Vec<(String, String)>,
+
+    /// Remove the files in the shm directory matching the given wildcard
+    /// pattern, can be given multiple times. Only files with the temporary
+    /// attribute the bridge sets are removed. If no maps are given, only the
+    /// matching files are removed. With `--dry-run` every line contains the
+    /// reason `glob` as well.
+    #[arg(long, value_name = "PATTERN", conflicts_with = "from_pid_file")]
+    pub glob: Vec<String>,
+
+    /// Allow `--glob` patterns which match every file, like `*`.
+    #[arg(short, long, requires = "glob")]
+    pub force: bool,
+}
+
+/// The arguments of the `dump` subcommand.
+#[derive(Args)]
+pub struct DumpArgs {
+    /// The name of the map which should be dumped.
+    #[arg(value_name = "MAPNAME")]
+    pub name: String,
+
+    /// The number of bytes that should be dumped. The hex dump prints 256
+    /// bytes by default, while `--out` writes the whole content.
+    #[arg(long, value_parser = parse_size)]
+    pub bytes: Option<usize>,
+
+    /// The interval, in seconds, in which the hex dump is printed.
+    #[arg(long, default_value_t = 1.0, conflicts_with = "out")]
+    pub interval: f64,
+
+    /// Write the content of the map to the given path once, instead of
+    /// periodically printing a hex dump until CTRL-C is pressed.
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+}
+
+/// The arguments of the `list` subcommand.
+#[derive(Args)]
+pub struct ListArgs {
+    /// Only list files which were created with the temporary file attribute,
+    /// like the bridge does. Wine can only report the attribute if the
+    /// filesystem supports extended attributes.
+    #[arg(long)]
+    pub temporary: bool,
+
+    /// Only list the files of the maps found in the given TOML or JSON config
+    /// file.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// The format of the config file, by default files with a `.json`
+    /// extension are parsed as JSON and every other file as TOML.
+    #[arg(long, value_enum, requires = "config")]
+    pub config_format: Option<ConfigFormat>,
+}
+
+/// The arguments of the `check` subcommand.
+#[derive(Args)]
+pub struct CheckArgs {
+    #[command(flatten)]
+    pub maps: MapArgs,
+}
+
+/// The arguments of the `info` subcommand.
+#[derive(Args)]
+pub struct InfoArgs {
+    #[command(flatten)]
+    pub maps: MapArgs,
+}
+
+/// The arguments of the `selftest` subcommand.
+#[derive(Args)]
+pub struct SelftestArgs {
+    /// The size of the temporary map, the K, M, and G suffixes can be used.
+    #[arg(long, value_parser = parse_size, default_value = "64M")]
+    pub size: usize,
+}
+
+/// The arguments which were used before the subcommands existed.
+///
+/// They are still accepted if no subcommand is given, the arguments of the
+/// `create` subcommand are used as is, while the rest select one of the
+/// other subcommands.
+#[derive(Args)]
+struct LegacyArgs {
+    #[command(flatten)]
+    create: CreateArgs,
+
+    /// Use the `clean` subcommand instead.
+    #[arg(short, long, hide = true)]
+    clean_up: bool,
+
+    /// Use `clean --from-pid-file` instead.
+    #[arg(
+        long,
+        hide = true,
+        requires = "clean_up",
+        conflicts_with_all = ["map", "size", "size_all", "config", "preset", "shm_dir"]
+    )]
+    from_pid_file: Option<PathBuf>,
+
+    /// Use `clean --glob` instead.
+    #[arg(long, hide = true, value_name = "PATTERN", requires = "clean_up")]
+    glob: Vec<String>,
+
+    /// Use the `dump` subcommand instead.
+    #[arg(long, hide = true, value_name = "MAPNAME", conflicts_with_all = ["clean_up", "dry_run"])]
+    dump: Option<String>,
+
+    /// Use `dump --bytes` instead.
+    #[arg(long, hide = true, value_parser = parse_size, requires = "dump")]
+    dump_bytes: Option<usize>,
+
+    /// Use `dump --interval` instead.
+    #[arg(long, hide = true, default_value_t = 1.0, requires = "dump")]
+    interval: f64,
+
+    /// Use `dump --out` instead.
+    #[arg(
+        long,
+        hide = true,
+        value_name = "MAPNAME",
+        requires = "out",
+        conflicts_with_all = ["clean_up", "dry_run", "dump"]
+    )]
+    snapshot: Option<String>,
+
+    /// Use `dump --out` instead.
+    #[arg(long, hide = true, requires = "snapshot")]
+    out: Option<PathBuf>,
+
+    /// Use `dump --bytes` instead.
+    #[arg(long, hide = true, value_parser = parse_size, requires = "snapshot")]
+    snapshot_bytes: Option<usize>,
+}
+
+impl LegacyArgs {
+    /// Turn the legacy arguments into the subcommand they select.
+    fn into_command(self) -> Command {
+        if let Some(name) = self.dump {
+            Command::Dump(DumpArgs {
+                name,
+                bytes: self.dump_bytes,
+                interval: self.interval,
+                out: None,
+            })
+        } else if let Some(name) = self.snapshot {
+            Command::Dump(DumpArgs {
+                name,
+                bytes: self.snapshot_bytes,
+                interval: self.interval,
+                out: self.out,
+            })
+        } else if self.clean_up {
+            Command::Clean(CleanArgs {
+                maps: self.create.maps,
+                dry_run: self.create.dry_run,
+                from_pid_file: self.from_pid_file,
+                prune: false,
+                alias: self.create.alias,
+                glob: self.glob,
+                force: self.create.force,
+            })
+        } else {
+            Command::Create(self.create)
+        }
+    }
+}
+
+/// Parse an `--init-from` argument in the `name=path` form.
+fn parse_init_from(input: &str) -> Result<(String, PathBuf), String> {
+    match input.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+            Ok((name.to_owned(), PathBuf::from(path)))
+        }
+        _ => Err(format!("The argument `{input}` needs to be given in the `name=path` form")),
+    }
+}
+
+/// Parse an `--alias` argument in the `name=alias` form.
+///
+/// The alias is used as a file name, so it can't contain any path separators.
+fn parse_alias(input: &str) -> Result<(String, String), String> {
+    match input.split_once('=') {
+        Some((name, alias)) if !name.is_empty() && !alias.is_empty() => {
+            if alias.contains(['/', '\\']) || alias == "." || alias == ".." {
+                Err(format!("The alias `{alias}` needs to be a plain file name"))
+            } else {
+                Ok((name.to_owned(), alias.to_owned()))
+            }
+        }
+        _ => Err(format!("The argument `{input}` needs to be given in the `name=alias` form")),
+    }
+}
+
+/// Parse a `--base-address` argument in the `name=address` form.
+fn parse_base_address(input: &str) -> Result<(String, usize), String> {
+    match input.split_once('=') {
+        Some((name, address)) if !name.is_empty() => Ok((name.to_owned(), parse_address(address)?)),
+        _ => Err(format!("The argument `{input}` needs to be given in the `name=address` form")),
+    }
+}
+
+/// Parse a `--prefix` argument, the prefix becomes part of file names, so it
+/// can't contain any path separators.
+fn parse_prefix(input: &str) -> Result<String, String> {
+    if input.contains(['/', '\\']) {
+        Err(format!("The prefix `{input}` can't contain path separators"))
+    } else {
+        Ok(input.to_owned())
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::map_spec::{MapEntry, MapSpec};
+
+/// The format of a [`Config`] file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ConfigFormat {
+    /// A TOML file, this is the default.
+    Toml,
+    /// A JSON file using the same structure as the TOML file.
+    Json,
+}
+
+impl ConfigFormat {
+    /// Guess the format of the config file at the given path from its
+    /// extension, files without a `.json` extension are assumed to be TOML.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+}
+
+/// The configuration file of the bridge.
+///
+/// The configuration file is a TOML file containing a list of maps the bridge
+/// should manage:
+///
+/// ```toml
+/// [[map]]
+/// name = "iRacingTelemetry"
+/// size = "32M"
+///
+/// [[map]]
+/// name = "acpmf_static"
+/// size = 2048
+/// ```
+///
+/// A map is writable unless its `access` is set to `ro`, see
+/// [`Access`](crate::map_spec::Access). The `base_address` of a map, see
+/// [`MapEntry::base_address`], is only meant for compatibility experiments.
+///
+/// Patterns matching the files of maps which were removed from the config can
+/// be given as well, the `clean` subcommand removes those files if `--prune` is
+/// given:
+///
+/// ```toml
+/// prune = ["acpmf_*", "iRacing*"]
+/// ```
+///
+/// The same structure can be given as a JSON file as well, see
+/// [`ConfigFormat::Json`]:
+///
+/// ```json
+/// {
+///     "map": [
+///         { "name": "iRacingTelemetry", "size": "32M" },
+///         { "name": "acpmf_static", "size": 2048 }
+///     ],
+///     "prune": ["acpmf_*"]
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Config {
+    /// The maps the bridge should manage.
+    pub maps: Vec<MapSpec>,
+    /// Wildcard patterns matching the `/dev/shm` backed files of maps which
+    /// aren't managed anymore.
+    pub prune: Vec<String>,
+}
+
+/// The [`Config`] as it's written in the file, before the sizes of the maps
+/// are interpreted.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(rename = "map", default)]
+    maps: Vec<MapEntry>,
+    #[serde(default)]
+    prune: Vec<String>,
+}
+
+/// Load the [`Config`] found at the given path.
+///
+/// If no format is given, it's guessed from the extension of the file using
+/// [`ConfigFormat::from_path()`]. The suffixes of the sizes use decimal
+/// multipliers if `si_units` is set.
+pub fn load_config(path: &Path, format: Option<ConfigFormat>, si_units: bool) -> Result<Config> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read the config file {}", path.display()))?;
+
+    let config: Result<ConfigFile> = match format.unwrap_or_else(|| ConfigFormat::from_path(path)) {
+        ConfigFormat::Toml => toml::from_str(&content).map_err(anyhow::Error::from),
+        ConfigFormat::Json => serde_json::from_str(&content).map_err(anyhow::Error::from),
+    };
+    let ConfigFile { maps, prune } =
+        config.with_context(|| format!("Could not parse the config file {}", path.display()))?;
+
+    Ok(Config { maps: maps.into_iter().map(|map| map.resolve(si_units)).collect(), prune })
+}
+
+/// The name of the config file which is looked for next to the executable.
+const EXE_CONFIG_NAME: &str = "shm-bridge.toml";
+
+/// The locations a config file is looked for if no maps were given, in the
+/// order they're tried.
+///
+/// The first location is `shm-bridge.toml` next to the executable, the second
+/// one `shm-bridge/maps.toml` inside the Linux config directory, which is
+/// `$XDG_CONFIG_HOME` or `$HOME/.config`, accessed through the `Z:` drive.
+pub fn default_config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    if let Some(dir) = std::env::current_exe().ok().as_deref().and_then(Path::parent) {
+        paths.push(dir.join(EXE_CONFIG_NAME));
+    }
+
+    // Wine passes the Linux environment on to Windows processes.
+    let config_home = std::env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|dir| dir.starts_with('/'))
+        .or_else(|| std::env::var("HOME").ok().map(|home| format!("{home}/.config")))
+        .filter(|dir| dir.starts_with('/'));
+
+    if let Some(dir) = config_home {
+        paths.push(PathBuf::from(format!("Z:{dir}/shm-bridge/maps.toml")));
+    }
+
+    paths
+}
+
+/// Find the first of the [`default_config_paths()`] which exists.
+pub fn find_default_config() -> Option<PathBuf> {
+    default_config_paths().into_iter().find(|path| path.is_file())
+}
@@ -0,0 +1,120 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Detect Linux processes which have the `/dev/shm` backed files mapped.
+//!
+//! Every process lists the files it has mapped into its memory in
+//! `/proc/<pid>/maps`, this includes the simulator, since Wine maps the file
+//! backing a file mapping object when a view of it is created, as well as
+//! Linux applications reading the files using `mmap(2)`.
+
+use std::{
+    ffi::OsString,
+    fs::{read_dir, read_link, read_to_string},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use log::{debug, info};
+
+use crate::shutdown::Shutdown;
+
+/// How often the consumers are looked up.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Get the path of a file the way it shows up in `/proc/<pid>/maps`.
+fn linux_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Get the Linux PID of our own process.
+///
+/// Under Wine the PID of the Windows process doesn't match the Linux one, but
+/// `/proc/self` still points to our Linux process.
+fn own_pid() -> Option<OsString> {
+    read_link("/proc/self").ok().map(PathBuf::into_os_string)
+}
+
+/// Check if any process, besides our own, has one of the given files mapped
+/// into its memory.
+///
+/// Processes whose memory maps we aren't allowed to read are ignored.
+pub fn has_consumers(paths: &[PathBuf]) -> bool {
+    let paths: Vec<_> = paths.iter().map(|path| linux_path(path)).collect();
+    let own_pid = own_pid();
+
+    let Ok(processes) = read_dir("/proc") else {
+        return false;
+    };
+
+    processes.filter_map(Result::ok).any(|process| {
+        let file_name = process.file_name();
+        let is_pid = file_name.to_string_lossy().bytes().all(|c| c.is_ascii_digit());
+
+        is_pid
+            && own_pid.as_ref() != Some(&file_name)
+            && read_to_string(process.path().join("maps")).is_ok_and(|maps| {
+                maps.lines().any(|line| paths.iter().any(|path| line.ends_with(path.as_str())))
+            })
+    })
+}
+
+/// Block until a consumer has attached to one of the given files and all the
+/// consumers have been detached for at least the given grace period.
+///
+/// Returns early if a shutdown is requested.
+pub fn wait_for_consumers(shutdown: &Shutdown, paths: &[PathBuf], grace_period: Duration) {
+    info!("Waiting for a consumer to attach to the mappings.");
+
+    while !has_consumers(paths) {
+        if shutdown.wait_timeout(POLL_INTERVAL) {
+            return;
+        }
+    }
+
+    info!("A consumer has attached to the mappings.");
+
+    let mut detached_since = None;
+
+    loop {
+        if has_consumers(paths) {
+            if detached_since.take().is_some() {
+                debug!("A consumer has attached to the mappings again");
+            }
+        } else {
+            let detached_since = *detached_since.get_or_insert_with(|| {
+                info!(
+                    "All consumers have detached, shutting down in {} seconds unless one \
+                     attaches again.",
+                    grace_period.as_secs_f64()
+                );
+                Instant::now()
+            });
+
+            if detached_since.elapsed() >= grace_period {
+                return;
+            }
+        }
+
+        if shutdown.wait_timeout(POLL_INTERVAL) {
+            return;
+        }
+    }
+}
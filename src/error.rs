@@ -0,0 +1,273 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::{io, path::PathBuf};
+
+/// The errors the bridge can run into, exported as
+/// [`BridgeError`](crate::BridgeError) as well.
+///
+/// The clean up functions, i.e. [`clean_up()`](crate::clean_up), return it
+/// directly. The rest of the core functions return an [`anyhow::Error`] which
+/// contains it, possibly beneath some context, if the cause of the failure is
+/// known, [`Error::find()`] looks it up.
+///
+/// Some errors have their own exit code, every other error makes the bridge
+/// exit with [`EXIT_FAILURE`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The number of maps without an inline size doesn't match the number of
+    /// `--size` arguments.
+    #[error(
+        "The number of maps without an inline size ({maps}) doesn't match the number of sizes \
+         ({sizes}), each `--map` needs a matching `--size` or has to use the `name:size` syntax"
+    )]
+    CountMismatch {
+        /// The number of maps without an inline size.
+        maps: usize,
+        /// The number of `--size` arguments.
+        sizes: usize,
+    },
+    /// The same map name was given multiple times.
+    #[error(
+        "The map {name} was given multiple times, once with size {first_size} and once with size \
+         {second_size}, use --dedupe to keep the last definition"
+    )]
+    DuplicateMap {
+        /// The name of the map.
+        name: String,
+        /// The size of the first definition of the map.
+        first_size: usize,
+        /// The size of the second definition of the map.
+        second_size: usize,
+    },
+    /// The name of a map can't be used as a file name.
+    #[error("{reason}")]
+    InvalidMapName {
+        /// The reason why the name is invalid.
+        reason: String,
+    },
+    /// The size of a map exceeds the maximum size, which usually means the
+    /// size has a typo.
+    #[error(
+        "The map {name} has a size of {size} bytes, which is larger than the maximum of \
+         {max_size} bytes, use --allow-huge if this is intended or change the maximum using \
+         --max-size"
+    )]
+    MapTooLarge {
+        /// The name of the map.
+        name: String,
+        /// The requested size of the map in bytes.
+        size: usize,
+        /// The maximum size of a map in bytes.
+        max_size: usize,
+    },
+    /// The shm directory the `/dev/shm` backed files should be placed in
+    /// doesn't exist.
+    #[error("The shm directory {} doesn't exist or isn't a directory", path.display())]
+    TmpfsNotFound {
+        /// The path of the shm directory.
+        path: PathBuf,
+    },
+    /// The `/dev/shm` backed file of a map couldn't be created or opened.
+    #[error("Could not open the tmpfs file: {path:?}")]
+    FileCreate {
+        /// The path of the file.
+        path: PathBuf,
+        /// The error opening the file.
+        source: io::Error,
+    },
+    /// The Wine user isn't allowed to create, modify, or remove a file in the
+    /// shm directory.
+    #[error(
+        "The Wine user isn't allowed to modify {}. Wine runs as the Linux user which started it, \
+         check the ownership and the mode of the directory {dir}, i.e. using `ls -ld {dir}`, or \
+         select a different directory using --shm-dir",
+        path.display(),
+        dir = path.parent().unwrap_or(path).display()
+    )]
+    PermissionDenied {
+        /// The path of the file.
+        path: PathBuf,
+        /// The error accessing the file.
+        source: io::Error,
+    },
+    /// The Win32 file mapping couldn't be created.
+    #[error("Failed to create the FileMapping: {reason}")]
+    MappingCreate {
+        /// The description of the Windows error, including the error code.
+        reason: String,
+    },
+    /// The `/dev/shm` backed file of a map already exists with a different
+    /// size.
+    #[error(
+        "The existing file {} of the map {name} has a size of {existing} bytes, but a size of \
+         {requested} bytes was requested, use --force to recreate the file",
+        path.display()
+    )]
+    SizeMismatch {
+        /// The name of the map.
+        name: String,
+        /// The path of the existing `/dev/shm` backed file.
+        path: PathBuf,
+        /// The size of the existing file in bytes.
+        existing: u64,
+        /// The requested size of the map in bytes.
+        requested: usize,
+    },
+    /// Existing `/dev/shm` backed files have a different size than requested,
+    /// and the bridge was told to refuse to start in that case.
+    #[error(
+        "The existing files of {} {} have a different size than requested:{}",
+        mismatches.len(),
+        if mismatches.len() == 1 { "map" } else { "maps" },
+        list_mismatches(mismatches)
+    )]
+    StrictSizeMismatch {
+        /// The maps whose files have the wrong size.
+        mismatches: Vec<SizeMismatch>,
+    },
+    /// The `check` subcommand found maps which are missing or have the wrong
+    /// size.
+    #[error("{failed} of the maps are missing or have the wrong size")]
+    CheckFailed {
+        /// The number of maps that failed the check.
+        failed: usize,
+    },
+    /// The mappings of some maps couldn't be created, while the rest of the
+    /// maps were created.
+    #[error(
+        "The mappings of {} {} couldn't be created:{}",
+        failures.len(),
+        if failures.len() == 1 { "map" } else { "maps" },
+        list_failures(failures)
+    )]
+    CreateFailed {
+        /// The names of the maps that couldn't be created together with the
+        /// reason.
+        failures: Vec<(String, anyhow::Error)>,
+    },
+    /// The `/dev/shm` backed files of some maps couldn't be removed.
+    #[error(
+        "The files of {} {} couldn't be removed:{}",
+        failures.len(),
+        if failures.len() == 1 { "map" } else { "maps" },
+        list_failures(failures)
+    )]
+    CleanupFailed {
+        /// The names of the maps that couldn't be removed together with the
+        /// reason.
+        failures: Vec<(String, anyhow::Error)>,
+    },
+    /// We're running on native Windows instead of under Wine.
+    #[error(
+        "shm-bridge isn't running under Wine, it only works under Wine or Proton on Linux, where \
+         it bridges the Windows shared memory to /dev/shm. On native Windows the shared memory \
+         is already accessible to other Windows applications, so there's nothing to bridge"
+    )]
+    NotWine,
+    /// The `/dev/shm` backed file of a map doesn't exist.
+    #[error("The /dev/shm backed file {} of the map {name} doesn't exist", path.display())]
+    MissingMap {
+        /// The name of the map.
+        name: String,
+        /// The path of the `/dev/shm` backed file we expected to find.
+        path: PathBuf,
+    },
+}
+
+/// An existing `/dev/shm` backed file whose size differs from the requested
+/// size of its map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeMismatch {
+    /// The name of the map.
+    pub name: String,
+    /// The path of the existing `/dev/shm` backed file.
+    pub path: PathBuf,
+    /// The size of the existing file in bytes.
+    pub existing: u64,
+    /// The requested size of the map in bytes.
+    pub requested: usize,
+}
+
+/// The exit code used for errors that don't have a specific exit code, like
+/// failures to create a mapping.
+pub const EXIT_FAILURE: u8 = 1;
+
+impl Error {
+    /// Find the [`Error`] in the chain of the given error, if the cause of the
+    /// failure is known.
+    pub fn find(error: &anyhow::Error) -> Option<&Error> {
+        error.chain().find_map(|e| e.downcast_ref::<Error>())
+    }
+
+    /// Get the exit code the bridge should exit with if this error occurs.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::CountMismatch { .. }
+            | Error::DuplicateMap { .. }
+            | Error::InvalidMapName { .. }
+            | Error::MapTooLarge { .. } => 2,
+            Error::MissingMap { .. } => 3,
+            Error::SizeMismatch { .. } | Error::StrictSizeMismatch { .. } => 4,
+            Error::CheckFailed { .. } => 5,
+            Error::TmpfsNotFound { .. }
+            | Error::FileCreate { .. }
+            | Error::PermissionDenied { .. }
+            | Error::MappingCreate { .. }
+            | Error::CreateFailed { .. }
+            | Error::NotWine => EXIT_FAILURE,
+            // If all the files were missing, we can use the more specific exit code.
+            Error::CleanupFailed { failures } => {
+                if failures.iter().all(|(_, e)| exit_code(e) == 3) {
+                    3
+                } else {
+                    EXIT_FAILURE
+                }
+            }
+        }
+    }
+}
+
+/// Format the failures of multiple maps, every map is put on its own indented
+/// line.
+fn list_failures(failures: &[(String, anyhow::Error)]) -> String {
+    failures.iter().map(|(name, e)| format!("\n    {name}: {e:#}")).collect()
+}
+
+/// Format the given size mismatches as an indented list, one mismatch per line.
+fn list_mismatches(mismatches: &[SizeMismatch]) -> String {
+    mismatches
+        .iter()
+        .map(|SizeMismatch { name, path, existing, requested }| {
+            format!(
+                "\n    {name}: {} has {existing} bytes, {requested} bytes were requested",
+                path.display()
+            )
+        })
+        .collect()
+}
+
+/// Get the exit code for the given error.
+///
+/// The whole chain of the error is searched for an [`Error`], if none is found
+/// [`EXIT_FAILURE`] is returned.
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    Error::find(error).map_or(EXIT_FAILURE, Error::exit_code)
+}
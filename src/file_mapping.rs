@@ -18,18 +18,63 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use std::{fs::File, os::windows::prelude::AsRawHandle};
+use std::{
+    ffi::c_void,
+    fs::File,
+    io::ErrorKind,
+    marker::PhantomData,
+    os::windows::prelude::AsRawHandle,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{anyhow, Context, Result};
 use windows::{
-    core::HSTRING,
+    core::{HSTRING, PCWSTR},
     Wdk::System::SystemServices::PAGE_READWRITE,
     Win32::{
-        Foundation::{CloseHandle, HANDLE},
-        System::Memory::{CreateFileMappingW, PAGE_PROTECTION_FLAGS},
+        Foundation::{CloseHandle, ERROR_ALREADY_EXISTS, HANDLE},
+        System::{
+            Memory::{
+                CreateFileMappingW, FlushViewOfFile, MapViewOfFile, MapViewOfFileEx,
+                OpenFileMappingW, UnmapViewOfFile, FILE_MAP, FILE_MAP_READ, FILE_MAP_WRITE,
+                MEMORY_MAPPED_VIEW_ADDRESS, PAGE_PROTECTION_FLAGS, PAGE_READONLY,
+            },
+            SystemInformation::{GetSystemInfo, SYSTEM_INFO},
+        },
     },
 };
 
+use crate::error::Error;
+
+/// The access other processes, and we, get to a [`FileMapping`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Protection {
+    /// The mapping can be read and written, this is what simulators expect.
+    #[default]
+    ReadWrite,
+    /// The mapping can only be read, views which should be writable can't be
+    /// mapped.
+    ReadOnly,
+}
+
+impl Protection {
+    /// The page protection the mapping is created with.
+    fn page_protection(self) -> PAGE_PROTECTION_FLAGS {
+        match self {
+            Protection::ReadWrite => PAGE_PROTECTION_FLAGS(PAGE_READWRITE),
+            Protection::ReadOnly => PAGE_READONLY,
+        }
+    }
+
+    /// The access views of the mapping are mapped with.
+    fn access(self) -> FILE_MAP {
+        match self {
+            Protection::ReadWrite => FILE_MAP_WRITE,
+            Protection::ReadOnly => FILE_MAP_READ,
+        }
+    }
+}
+
 /// File-backed named shared memory[1].
 ///
 /// This will create named shared memory backed by a file.
@@ -43,6 +88,15 @@ use windows::{
 /// [1]: https://learn.microsoft.com/en-us/windows/win32/memory/creating-named-shared-memory
 pub struct FileMapping {
     handle: HANDLE,
+    size: usize,
+    access: FILE_MAP,
+    already_existed: bool,
+    /// The address views should be mapped at, see
+    /// [`FileMapping::set_base_address()`].
+    base_address: Option<usize>,
+    /// The backing file which should be removed once the [`FileMapping`] is
+    /// dropped.
+    owned_file: Option<PathBuf>,
 }
 
 impl FileMapping {
@@ -55,16 +109,25 @@ impl FileMapping {
     ///   applications can open the shared memory using this name.
     ///
     /// * `file` - The file that should be used as the backing storage of the
-    ///   [`FileMapping`]. The file will be resized to have the correct length.
+    ///   [`FileMapping`]. The file will be extended if it's shorter than the
+    ///   [`FileMapping`], longer files are left alone.
     ///
     /// * `size` - The desiered size the [`FileMapping`] should have, i.e. the
     ///   number of bytes the [`FileMapping`] should have.
-    pub fn new(name: &str, file: &File, size: usize) -> Result<Self> {
-        // Ensure the file is of the correct size.
-        file.set_len(size as u64).context("Couldn't set the file size of the FileMapping.")?;
+    ///
+    /// * `protection` - Can the [`FileMapping`] be written to, a read-only
+    ///   [`FileMapping`] only needs a file which was opened for reading.
+    pub fn new(name: &str, file: &File, size: usize, protection: Protection) -> Result<Self> {
+        // Ensure the file is large enough, a file which has been rounded up to the
+        // page size must not be truncated again.
+        let file_size =
+            file.metadata().context("Couldn't query the file size of the FileMapping.")?.len();
+
+        if file_size < size as u64 {
+            file.set_len(size as u64).context("Couldn't set the file size of the FileMapping.")?;
+        }
 
-        let high_size: u32 = ((size as u64 & 0xFFFF_FFFF_0000_0000_u64) >> 32) as u32;
-        let low_size: u32 = (size as u64 & 0xFFFF_FFFF_u64) as u32;
+        let (high_size, low_size) = split_size(size);
 
         // Windows uses UTF-16, so we need to convert the UTF-8 based Rust string
         // accordingly.
@@ -75,7 +138,7 @@ impl FileMapping {
             CreateFileMappingW(
                 handle,
                 None,
-                PAGE_PROTECTION_FLAGS(PAGE_READWRITE),
+                protection.page_protection(),
                 high_size,
                 low_size,
                 &name,
@@ -83,15 +146,390 @@ impl FileMapping {
         };
 
         match handle {
-            Ok(handle) => Ok(FileMapping { handle }),
-            Err(e) => Err(anyhow!("Failed to create the FileMapping: {e}")),
+            Ok(handle) => {
+                // If a mapping with the same name exists, we get a handle to the existing
+                // mapping, which isn't necessarily backed by our file.
+                let already_existed =
+                    windows::core::Error::from_win32().code() == ERROR_ALREADY_EXISTS.to_hresult();
+
+                Ok(FileMapping {
+                    handle,
+                    size,
+                    access: protection.access(),
+                    already_existed,
+                    base_address: None,
+                    owned_file: None,
+                })
+            }
+            Err(e) => Err(Error::MappingCreate { reason: describe_error(&e) }.into()),
         }
     }
+
+    /// Did a mapping with the same name already exist when this [`FileMapping`]
+    /// was created.
+    ///
+    /// In this case the [`FileMapping`] refers to the existing mapping, which
+    /// might not be backed by the file that was given to
+    /// [`FileMapping::new()`].
+    pub fn already_existed(&self) -> bool {
+        self.already_existed
+    }
+
+    /// The size of the [`FileMapping`] in bytes, as it was passed to
+    /// [`FileMapping::new()`].
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Was the [`FileMapping`] created with [`Protection::ReadOnly`].
+    pub fn is_read_only(&self) -> bool {
+        self.access == FILE_MAP_READ
+    }
+
+    /// Take ownership of the backing file at the given path, the file will be
+    /// removed once the [`FileMapping`] is dropped.
+    ///
+    /// This ensures the file is removed even if we panic, errors while removing
+    /// the file are ignored.
+    pub fn own_file(&mut self, path: impl Into<PathBuf>) {
+        self.owned_file = Some(path.into());
+    }
+
+    /// Give up the ownership of the backing file, it won't be removed once the
+    /// [`FileMapping`] is dropped.
+    ///
+    /// Returns the path of the previously owned file.
+    pub fn release_file(&mut self) -> Option<PathBuf> {
+        self.owned_file.take()
+    }
+
+    /// Create a new, unnamed and read-only, [`FileMapping`] of an existing
+    /// file.
+    ///
+    /// The [`FileMapping`] spans the whole file, so this allows us to inspect
+    /// the content of an existing `/dev/shm` backed file.
+    pub fn open_read_only(file: &File) -> Result<Self> {
+        let size = file.metadata().context("Couldn't query the size of the file.")?.len();
+        let size = usize::try_from(size).context("The file is too large to be mapped.")?;
+
+        if size == 0 {
+            return Err(anyhow!("The file is empty and can't be mapped."));
+        }
+
+        let (high_size, low_size) = split_size(size);
+        let handle = HANDLE(file.as_raw_handle() as _);
+
+        let handle = unsafe {
+            CreateFileMappingW(handle, None, PAGE_READONLY, high_size, low_size, PCWSTR::null())
+        };
+
+        match handle {
+            Ok(handle) => Ok(FileMapping {
+                handle,
+                size,
+                access: FILE_MAP_READ,
+                already_existed: false,
+                base_address: None,
+                owned_file: None,
+            }),
+            Err(e) => Err(Error::MappingCreate { reason: describe_error(&e) }.into()),
+        }
+    }
+
+    /// Open an existing named [`FileMapping`], which another process created,
+    /// for reading.
+    ///
+    /// Views of the [`FileMapping`] span the given number of bytes, mapping a
+    /// view fails if the existing mapping is smaller.
+    pub fn open_named(name: &str, size: usize) -> Result<Self> {
+        let name = HSTRING::from(name);
+
+        match unsafe { OpenFileMappingW(FILE_MAP_READ.0, false, &name) } {
+            Ok(handle) => Ok(FileMapping {
+                handle,
+                size,
+                access: FILE_MAP_READ,
+                already_existed: true,
+                base_address: None,
+                owned_file: None,
+            }),
+            Err(e) => Err(anyhow!("Failed to open the FileMapping: {}", describe_error(&e))),
+        }
+    }
+
+    /// Map the views of the [`FileMapping`] at the given address, using
+    /// [`MapViewOfFileEx`], instead of letting the system pick one.
+    ///
+    /// The address needs to be a multiple of the allocation granularity, and
+    /// the address range needs to be free, otherwise mapping a view fails.
+    /// This only affects our own views, other processes map their views
+    /// wherever they want.
+    pub fn set_base_address(&mut self, base_address: Option<usize>) {
+        self.base_address = base_address;
+    }
+
+    /// Map a view of the whole [`FileMapping`] into our address space.
+    pub fn view(&self) -> Result<View<'_>> {
+        let address = match self.base_address {
+            Some(base_address) => unsafe {
+                MapViewOfFileEx(
+                    self.handle,
+                    self.access,
+                    0,
+                    0,
+                    self.size,
+                    Some(base_address as *const c_void),
+                )
+            },
+            None => unsafe { MapViewOfFile(self.handle, self.access, 0, 0, self.size) },
+        };
+
+        if address.Value.is_null() {
+            let reason = describe_error(&windows::core::Error::from_win32());
+
+            Err(match self.base_address {
+                Some(base_address) => anyhow!(
+                    "Failed to map a view of the FileMapping at the address {base_address:#x}: \
+                     {reason}"
+                ),
+                None => anyhow!("Failed to map a view of the FileMapping: {reason}"),
+            })
+        } else {
+            Ok(View { address, size: self.size, _mapping: PhantomData })
+        }
+    }
+
+    /// Map a view we're going to write to, writing to the pages of a read-only
+    /// view would crash us.
+    fn writable_view(&self) -> Result<View<'_>> {
+        if self.is_read_only() {
+            return Err(anyhow!("The FileMapping is read-only and can't be written to."));
+        }
+
+        self.view()
+    }
+
+    /// Overwrite the whole content of the [`FileMapping`] with zeros.
+    ///
+    /// This writes to a view of the mapping, so the cleared memory is visible
+    /// to every other user of the mapping right away.
+    pub fn zero(&self) -> Result<()> {
+        let view = self.writable_view()?;
+
+        // Safety: The view is valid for `size` bytes as long as it is alive.
+        unsafe { std::ptr::write_bytes(view.as_ptr(), 0, view.len()) };
+
+        Ok(())
+    }
+
+    /// Copy the given bytes to the start of the [`FileMapping`].
+    ///
+    /// Bytes which don't fit into the mapping are ignored, the number of copied
+    /// bytes is returned.
+    pub fn write(&self, data: &[u8]) -> Result<usize> {
+        let view = self.writable_view()?;
+        let len = data.len().min(view.len());
+
+        // Safety: The view is valid for `size` bytes as long as it is alive, and we
+        // copy at most `size` bytes. The data can't overlap with the view since we
+        // just mapped it.
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), view.as_ptr(), len) };
+
+        Ok(len)
+    }
+
+    /// Write the modified pages of the [`FileMapping`] back to its file, using
+    /// `FlushViewOfFile()`.
+    ///
+    /// The pages are shared by all the views of the mapping, so flushing a new
+    /// view covers the writes done through earlier views as well.
+    pub fn flush(&self) -> Result<()> {
+        let view = self.view()?;
+
+        // Safety: The view is valid as long as it is alive, a size of zero flushes
+        // the whole view.
+        unsafe { FlushViewOfFile(view.as_ptr().cast_const().cast(), 0) }.map_err(|e| {
+            anyhow!("Failed to flush the view of the FileMapping: {}", describe_error(&e))
+        })
+    }
+}
+
+/// A view of a [`FileMapping`] that has been mapped into our address space.
+///
+/// The view is unmapped once the [`View`] object is dropped.
+pub struct View<'a> {
+    address: MEMORY_MAPPED_VIEW_ADDRESS,
+    size: usize,
+    _mapping: PhantomData<&'a FileMapping>,
+}
+
+impl View<'_> {
+    /// Get a pointer to the start of the mapped memory.
+    ///
+    /// Other processes might modify the memory at any time.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.address.Value.cast()
+    }
+
+    /// Get the number of bytes this view spans.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Does this view span zero bytes.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Copy the current content of the mapped memory.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.read(self.size)
+    }
+
+    /// Copy the first `bytes` bytes of the current content of the mapped
+    /// memory, or all of it if the view is smaller.
+    pub fn read(&self, bytes: usize) -> Vec<u8> {
+        // Safety: The view is valid for `size` bytes as long as it is alive.
+        unsafe { std::slice::from_raw_parts(self.as_ptr(), bytes.min(self.size)) }.to_vec()
+    }
+}
+
+impl Drop for View<'_> {
+    fn drop(&mut self) {
+        // There's not much we can do if an error happens here, so let's ignore it.
+        let _ = unsafe { UnmapViewOfFile(self.address) };
+    }
+}
+
+/// Describe a Windows error, including the numeric Win32 error code.
+///
+/// Wine doesn't have a message for every error code, and the messages it has
+/// are localized, so the code is what makes a bug report actionable.
+fn describe_error(error: &windows::core::Error) -> String {
+    let hresult = error.code().0 as u32;
+
+    // HRESULTs which wrap a Win32 error code use the `FACILITY_WIN32` facility,
+    // the Win32 error code is in the lower 16 bits.
+    let code = if hresult & 0xFFFF_0000 == 0x8007_0000 { hresult & 0xFFFF } else { hresult };
+    let message = error.message().to_string_lossy();
+    let message = message.trim();
+
+    if message.is_empty() {
+        format!("Win32 error {code} ({hresult:#010X})")
+    } else {
+        format!("{message} (Win32 error {code}, {hresult:#010X})")
+    }
+}
+
+/// Split the size into the high and low order `u32` values the Windows APIs
+/// expect.
+fn split_size(size: usize) -> (u32, u32) {
+    let high_size: u32 = ((size as u64 & 0xFFFF_FFFF_0000_0000_u64) >> 32) as u32;
+    let low_size: u32 = (size as u64 & 0xFFFF_FFFF_u64) as u32;
+
+    (high_size, low_size)
+}
+
+/// Query the memory layout of the system.
+fn system_info() -> SYSTEM_INFO {
+    let mut info = SYSTEM_INFO::default();
+
+    // Safety: GetSystemInfo only writes into the given struct.
+    unsafe { GetSystemInfo(&mut info) };
+
+    info
+}
+
+/// Get the page size of the system.
+pub fn page_size() -> usize {
+    system_info().dwPageSize as usize
+}
+
+/// Get the allocation granularity of the system, views of a mapping start at a
+/// multiple of it.
+pub fn allocation_granularity() -> usize {
+    system_info().dwAllocationGranularity as usize
+}
+
+/// Round the given size up to the next multiple of the page size.
+pub fn round_to_page_size(size: usize) -> usize {
+    size.next_multiple_of(page_size().max(1))
+}
+
+/// Get the size of an existing backing file of a [`FileMapping`].
+///
+/// Returns `None` if the file doesn't exist.
+pub fn existing_size(path: &Path) -> Result<Option<u64>> {
+    match std::fs::metadata(path) {
+        Ok(metadata) => Ok(Some(metadata.len())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).context(format!("Couldn't query the size of {}", path.display())),
+    }
 }
 
 impl Drop for FileMapping {
     fn drop(&mut self) {
         // There's not much we can do if an error happens here, so let's ignore it.
         let _ = unsafe { CloseHandle(self.handle) };
+
+        if let Some(path) = &self.owned_file {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use windows::Win32::Foundation::ERROR_ACCESS_DENIED;
+
+    use super::*;
+
+    #[test]
+    fn errors_contain_the_win32_code() {
+        let error = windows::core::Error::from(ERROR_ACCESS_DENIED.to_hresult());
+
+        let description = describe_error(&error);
+
+        assert!(description.contains("Win32 error 5"), "{description}");
+        assert!(description.contains("0x80070005"), "{description}");
+    }
+
+    #[test]
+    fn mapping_failures_contain_the_win32_code() {
+        let path = std::env::temp_dir().join("shm-bridge-test-empty-mapping");
+        let file =
+            File::options().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+
+        // Mapping an empty file fails if no size is given.
+        let error =
+            FileMapping::new("shm-bridge-test-empty-mapping", &file, 0, Protection::ReadWrite)
+                .err()
+                .unwrap();
+
+        drop(file);
+        std::fs::remove_file(path).unwrap();
+
+        assert!(format!("{error:#}").contains("Win32 error"), "{error:#}");
+    }
+
+    #[test]
+    fn read_only_mappings_refuse_writes() {
+        let path = std::env::temp_dir().join("shm-bridge-test-read-only-mapping");
+        std::fs::write(&path, [0; 16]).unwrap();
+        let file = File::open(&path).unwrap();
+
+        let mapping =
+            FileMapping::new("shm-bridge-test-read-only-mapping", &file, 16, Protection::ReadOnly)
+                .unwrap();
+
+        assert!(mapping.is_read_only());
+        assert!(mapping.write(b"data").is_err());
+        assert_eq!(mapping.view().unwrap().to_vec(), [0; 16]);
+        assert_eq!(mapping.view().unwrap().read(4), [0; 4]);
+        assert_eq!(mapping.view().unwrap().read(64), [0; 16]);
+
+        drop(mapping);
+        drop(file);
+        std::fs::remove_file(path).unwrap();
     }
 }
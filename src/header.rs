@@ -0,0 +1,167 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Read the size of a map from a value another map publishes.
+//!
+//! Some simulators publish the size of their telemetry map in a small
+//! discovery map, since the size depends on the car or the session.
+
+use clap::ValueEnum;
+
+use crate::size::parse_size;
+
+/// The width of a size stored in a discovery map.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SizeWidth {
+    /// A 32-bit unsigned integer.
+    #[default]
+    U32,
+    /// A 64-bit unsigned integer.
+    U64,
+}
+
+impl SizeWidth {
+    /// The number of bytes the size takes up.
+    pub fn bytes(self) -> usize {
+        match self {
+            SizeWidth::U32 => 4,
+            SizeWidth::U64 => 8,
+        }
+    }
+}
+
+/// The byte order of a size stored in a discovery map.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Endianness {
+    /// The least significant byte comes first, like on x86.
+    #[default]
+    Little,
+    /// The most significant byte comes first.
+    Big,
+}
+
+/// A map whose size is read from another map, given as `name=source:offset`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeFrom {
+    /// The name of the map that should be created.
+    pub name: String,
+    /// The name of the discovery map containing the size.
+    pub source: String,
+    /// The offset of the size inside the discovery map.
+    pub offset: usize,
+}
+
+/// Parse a `--size-from` argument in the `name=source:offset` form.
+///
+/// The offset is a number of bytes, either in decimal or prefixed with `0x` in
+/// hexadecimal.
+pub fn parse_size_from(input: &str) -> Result<SizeFrom, String> {
+    let invalid =
+        || format!("The argument `{input}` needs to be given in the `name=source:offset` form");
+
+    let (name, source) = input.split_once('=').ok_or_else(invalid)?;
+    let (source, offset) = source.rsplit_once(':').ok_or_else(invalid)?;
+
+    if name.is_empty() || source.is_empty() {
+        return Err(invalid());
+    }
+
+    let offset = match offset.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).map_err(|e| e.to_string()),
+        None => parse_size(offset),
+    }
+    .map_err(|e| format!("The offset of `{input}` isn't valid: {e}"))?;
+
+    Ok(SizeFrom { name: name.to_owned(), source: source.to_owned(), offset })
+}
+
+/// Decode the size found at the given offset of the content of a discovery
+/// map.
+pub fn decode_size(
+    content: &[u8],
+    offset: usize,
+    width: SizeWidth,
+    endianness: Endianness,
+) -> Result<usize, String> {
+    let bytes = offset
+        .checked_add(width.bytes())
+        .and_then(|end| content.get(offset..end))
+        .ok_or_else(|| {
+            format!(
+                "The size at offset {offset} doesn't fit into the {} bytes of the map",
+                content.len()
+            )
+        })?;
+
+    let size = match (width, endianness) {
+        (SizeWidth::U32, Endianness::Little) => {
+            u32::from_le_bytes(bytes.try_into().expect("The slice has 4 bytes")).into()
+        }
+        (SizeWidth::U32, Endianness::Big) => {
+            u32::from_be_bytes(bytes.try_into().expect("The slice has 4 bytes")).into()
+        }
+        (SizeWidth::U64, Endianness::Little) => {
+            u64::from_le_bytes(bytes.try_into().expect("The slice has 8 bytes"))
+        }
+        (SizeWidth::U64, Endianness::Big) => {
+            u64::from_be_bytes(bytes.try_into().expect("The slice has 8 bytes"))
+        }
+    };
+
+    match usize::try_from(size) {
+        Ok(0) => Err(format!("The size at offset {offset} is zero")),
+        Ok(size) => Ok(size),
+        Err(_) => Err(format!("The size {size} at offset {offset} is too large")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_size_from("telemetry=discovery:16"),
+            Ok(SizeFrom {
+                name: "telemetry".to_owned(),
+                source: "discovery".to_owned(),
+                offset: 16
+            })
+        );
+        assert_eq!(parse_size_from("telemetry=discovery:0x10").unwrap().offset, 16);
+        assert_eq!(parse_size_from(r"a=Local\b:4").unwrap().source, r"Local\b");
+        assert!(parse_size_from("telemetry=discovery").is_err());
+        assert!(parse_size_from("=discovery:4").is_err());
+        assert!(parse_size_from("telemetry:4").is_err());
+    }
+
+    #[test]
+    fn decode() {
+        let content = [0xff, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+        assert_eq!(decode_size(&content, 1, SizeWidth::U32, Endianness::Little), Ok(0x1000));
+        assert_eq!(decode_size(&content, 1, SizeWidth::U32, Endianness::Big), Ok(0x100000));
+        assert_eq!(decode_size(&content, 2, SizeWidth::U64, Endianness::Little), Ok(0x10));
+        assert!(decode_size(&content, 4, SizeWidth::U64, Endianness::Little).is_err());
+        assert!(decode_size(&content, 6, SizeWidth::U32, Endianness::Little).is_err());
+        assert!(decode_size(&content, usize::MAX, SizeWidth::U32, Endianness::Little).is_err());
+    }
+}
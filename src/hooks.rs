@@ -0,0 +1,89 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Run user supplied commands once the mappings are ready and before they're
+//! cleaned up.
+//!
+//! The commands are run by the Linux shell, Wine executes Linux binaries as
+//! native processes, so the hooks can start Linux applications, like a
+//! dashboard reading the `/dev/shm` backed files.
+
+use std::{
+    process::{Child, Command},
+    thread,
+};
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+
+/// The Linux shell which runs the hooks.
+const SHELL: &str = "/bin/sh";
+
+/// The environment variable which contains the Linux path of the shm
+/// directory.
+pub const DIR_ENV_VAR: &str = "SHM_BRIDGE_DIR";
+
+/// Start the given command using the Linux shell.
+fn spawn(command: &str, dir: Option<&str>) -> Result<Child> {
+    let mut shell = Command::new(SHELL);
+    shell.arg("-c").arg(command);
+
+    if let Some(dir) = dir {
+        shell.env(DIR_ENV_VAR, dir);
+    }
+
+    shell.spawn().with_context(|| format!("Could not run the hook `{command}` using {SHELL}"))
+}
+
+/// Log the exit status of a hook which finished.
+fn log_status(hook: &str, child: &mut Child) {
+    match child.wait() {
+        Ok(status) if status.success() => info!("The {hook} hook finished successfully"),
+        Ok(status) => warn!("The {hook} hook failed with {status}"),
+        Err(e) => warn!("Could not wait for the {hook} hook to finish: {e}"),
+    }
+}
+
+/// Start the given hook in the background, the bridge keeps running while it
+/// does.
+///
+/// The exit status of the hook is logged once it finishes, a failing hook
+/// never stops the bridge. The Linux path of the shm directory is passed to
+/// the hook using the [`DIR_ENV_VAR`] environment variable.
+pub fn start(hook: &'static str, command: &str, dir: Option<&str>) {
+    info!("Running the {hook} hook `{command}`");
+
+    match spawn(command, dir) {
+        Ok(mut child) => {
+            thread::spawn(move || log_status(hook, &mut child));
+        }
+        Err(e) => warn!("{e:#}"),
+    }
+}
+
+/// Run the given hook and wait for it to finish, see [`start()`].
+pub fn run(hook: &str, command: &str, dir: Option<&str>) {
+    info!("Running the {hook} hook `{command}`");
+
+    match spawn(command, dir) {
+        Ok(mut child) => log_status(hook, &mut child),
+        Err(e) => warn!("{e:#}"),
+    }
+}
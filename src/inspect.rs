@@ -0,0 +1,468 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::{
+    fmt::Write,
+    fs::{read_dir, remove_file, DirEntry, File},
+    io::ErrorKind,
+    os::windows::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info};
+use windows::Win32::Storage::FileSystem::FILE_ATTRIBUTE_TEMPORARY;
+
+use crate::{
+    bridge::{create_file_mapping, DEFAULT_FILE_ATTRIBUTES},
+    error::Error,
+    file_mapping::{
+        allocation_granularity, existing_size, page_size, round_to_page_size, FileMapping,
+        Protection,
+    },
+    map_spec::{self, MapSpec},
+    pattern,
+    retry::RetryPolicy,
+    shm_dir::ShmDir,
+    shutdown::Shutdown,
+    size::format_size,
+};
+
+/// The number of bytes printed on a single line of the hex dump.
+const BYTES_PER_LINE: usize = 16;
+
+/// The number of bytes in a MiB, used for the throughput of the self-test.
+const MIB: f64 = 1024.0 * 1024.0;
+
+/// The number of bytes the hex dump prints if no size was given.
+const DEFAULT_DUMP_BYTES: usize = 256;
+
+/// Open an existing `/dev/shm` backed file using the given prefix and create a
+/// read-only [`FileMapping`] spanning the whole file.
+fn open_existing(dir: &Path, prefix: &str, name: &str) -> Result<FileMapping> {
+    let path = dir.join(map_spec::file_name(name, prefix));
+
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(Error::MissingMap { name: name.to_owned(), path }.into());
+        }
+        Err(e) => {
+            return Err(e).with_context(|| format!("Could not open the tmpfs file {path:?}"));
+        }
+    };
+
+    FileMapping::open_read_only(&file)
+        .with_context(|| format!("Error creating a file mapping for {name}"))
+}
+
+/// Periodically print a hex dump of the first `bytes` bytes of an existing
+/// `/dev/shm` backed file until somebody asks us to shut down.
+///
+/// The first 256 bytes are printed if no size is given.
+pub fn dump(
+    dir: &Path,
+    prefix: &str,
+    name: &str,
+    bytes: Option<usize>,
+    interval: Duration,
+) -> Result<()> {
+    let bytes = bytes.unwrap_or(DEFAULT_DUMP_BYTES);
+    let mapping = open_existing(dir, prefix, name)?;
+    let view = mapping.view()?;
+
+    let shutdown = Shutdown::install()?;
+
+    loop {
+        let content = view.read(bytes);
+
+        println!("{name} ({} of {} bytes):\n{}", content.len(), view.len(), hex_dump(&content));
+
+        if shutdown.wait_timeout(interval) {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the current content of an existing `/dev/shm` backed file.
+pub fn read_map(dir: &Path, prefix: &str, name: &str) -> Result<Vec<u8>> {
+    Ok(open_existing(dir, prefix, name)?.view()?.to_vec())
+}
+
+/// Write the current content of an existing `/dev/shm` backed file to the
+/// given output path.
+///
+/// Only the first `bytes` bytes are written if given, otherwise the whole
+/// content is written.
+pub fn snapshot(
+    dir: &Path,
+    prefix: &str,
+    name: &str,
+    bytes: Option<usize>,
+    out: &Path,
+) -> Result<()> {
+    let mapping = open_existing(dir, prefix, name)?;
+    let content = mapping.view()?.read(bytes.unwrap_or(usize::MAX));
+
+    std::fs::write(out, &content)
+        .with_context(|| format!("Could not write the snapshot to {}", out.display()))?;
+
+    info!("Wrote a snapshot of {} bytes of {name} to {}", content.len(), out.display());
+
+    Ok(())
+}
+
+/// Print the path and the size of every file in the shm directory, besides
+/// the lock files of the bridge.
+///
+/// If `temporary` is set, only files with the temporary file attribute are
+/// printed. If `maps` is given, only the files of those maps, using the given
+/// prefix, are printed.
+pub fn list(dir: &ShmDir, prefix: &str, temporary: bool, maps: Option<&[MapSpec]>) -> Result<()> {
+    let path = dir.wine_path();
+
+    let entries = match read_dir(&path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            bail!(
+                "The shm directory {} isn't readable by the Wine user, check the permissions of \
+                 the directory or select a different one using --shm-dir",
+                dir.path.display()
+            );
+        }
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!("Could not read the shm directory {}", dir.path.display())
+            });
+        }
+    };
+
+    let file_names: Option<Vec<_>> =
+        maps.map(|maps| maps.iter().map(|map| map.file_name(prefix)).collect());
+
+    let mut files = Vec::new();
+
+    for entry in entries {
+        let entry = entry
+            .with_context(|| format!("Could not read the shm directory {}", dir.path.display()))?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+
+        if file_name.ends_with(".lock") {
+            continue;
+        }
+
+        if let Some(file_names) = &file_names {
+            if !file_names.contains(&file_name) {
+                continue;
+            }
+        }
+
+        // Files can disappear while we're looking at them, those aren't worth an
+        // error.
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+
+        if !metadata.is_file() {
+            continue;
+        }
+
+        if temporary && metadata.file_attributes() & FILE_ATTRIBUTE_TEMPORARY.0 == 0 {
+            continue;
+        }
+
+        files.push((file_name, metadata.len()));
+    }
+
+    files.sort();
+
+    for (file_name, size) in files {
+        println!("{} {size}", dir.path.join(file_name).display());
+    }
+
+    Ok(())
+}
+
+/// Find the files in the shm directory which match one of the given wildcard
+/// patterns, but don't belong to any of the given maps using the given prefix.
+///
+/// Only regular files are considered, lock files are skipped. Every orphaned
+/// file is returned together with the pattern it matched.
+pub fn orphans(
+    dir: &Path,
+    prefix: &str,
+    maps: &[MapSpec],
+    patterns: &[String],
+) -> Result<Vec<(PathBuf, String)>> {
+    let file_names: Vec<_> = maps.iter().map(|map| map.file_name(prefix)).collect();
+
+    matching_files(dir, patterns, |file_name, _| !file_names.iter().any(|name| name == file_name))
+}
+
+/// Find the files in the shm directory which match one of the given wildcard
+/// patterns and were created by the bridge, i.e. have the temporary file
+/// attribute.
+///
+/// Only regular files are considered, lock files are skipped. Every file is
+/// returned together with the pattern it matched.
+pub fn temporary_matching(dir: &Path, patterns: &[String]) -> Result<Vec<(PathBuf, String)>> {
+    matching_files(dir, patterns, |file_name, entry| {
+        // Files can disappear while we're looking at them, those aren't worth an
+        // error.
+        let temporary = entry
+            .metadata()
+            .is_ok_and(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_TEMPORARY.0 != 0);
+
+        if !temporary {
+            debug!("Skipping the file {file_name}, it doesn't have the temporary attribute");
+        }
+
+        temporary
+    })
+}
+
+/// Find the regular files in the shm directory which match one of the given
+/// wildcard patterns and are accepted by the `filter`, lock files are skipped.
+///
+/// The files are sorted by their path and returned together with the pattern
+/// they matched.
+fn matching_files(
+    dir: &Path,
+    patterns: &[String],
+    filter: impl Fn(&str, &DirEntry) -> bool,
+) -> Result<Vec<(PathBuf, String)>> {
+    let mut files = Vec::new();
+
+    let entries = read_dir(dir)
+        .with_context(|| format!("Could not read the shm directory {}", dir.display()))?;
+
+    for entry in entries {
+        let entry =
+            entry.with_context(|| format!("Could not read the shm directory {}", dir.display()))?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+
+        if file_name.ends_with(".lock")
+            || !entry.file_type().is_ok_and(|file_type| file_type.is_file())
+        {
+            continue;
+        }
+
+        let Some(pattern) = patterns.iter().find(|pattern| pattern::matches(pattern, &file_name))
+        else {
+            continue;
+        };
+
+        if filter(&file_name, &entry) {
+            files.push((dir.join(&file_name), pattern.clone()));
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+/// Check that the `/dev/shm` backed files of the given maps, using the given
+/// prefix, exist and have the expected size, the `padding` is added to the
+/// size of every map.
+///
+/// A line is printed for every map, containing the result of the check, the
+/// path, the size of the file, and in the case of a size mismatch the expected
+/// size, separated by spaces.
+pub fn check(dir: &Path, prefix: &str, maps: &[MapSpec], padding: usize) -> Result<()> {
+    let mut failed = 0;
+
+    for map in maps {
+        let path = dir.join(map.file_name(prefix));
+        let size = map.size.saturating_add(padding);
+
+        match existing_size(&path)? {
+            Some(existing)
+                if existing == size as u64 || existing == round_to_page_size(size) as u64 =>
+            {
+                println!("ok {} {existing}", path.display());
+            }
+            Some(existing) => {
+                println!("wrong-size {} {existing} {size}", path.display());
+                failed += 1;
+            }
+            None => {
+                println!("missing {}", path.display());
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        Err(Error::CheckFailed { failed }.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Print the page size and the allocation granularity of the system, and how
+/// the sizes of the given maps, including the padding, are rounded to them.
+///
+/// Every map is printed on a line containing its name, its size, the size of
+/// its `/dev/shm` backed file, which is rounded up to the page size, and the
+/// size rounded up to the allocation granularity, which is the address space
+/// a view of the map takes up.
+pub fn info(maps: &[MapSpec], padding: usize) {
+    let page_size = page_size();
+    let granularity = allocation_granularity();
+
+    println!("page-size {page_size}");
+    println!("allocation-granularity {granularity}");
+
+    for map in maps {
+        let size = map.size.saturating_add(padding);
+
+        println!(
+            "map {} {size} {} {}",
+            map.name,
+            round_to_page_size(size),
+            size.next_multiple_of(granularity.max(1))
+        );
+    }
+}
+
+/// Create, zero-fill, read back, and remove a temporary map of the given size
+/// in the given directory, printing how long each phase took.
+///
+/// The throughput of the fill and the read is printed in MiB/s. The temporary
+/// file is removed even if one of the phases fails.
+pub fn selftest(dir: &Path, size: usize, policy: RetryPolicy) -> Result<()> {
+    let name = format!("shm-bridge-selftest-{}", std::process::id());
+    let path = dir.join(map_spec::file_name(&name, ""));
+
+    if existing_size(&path)?.is_some() {
+        bail!("The self-test file {} already exists", path.display());
+    }
+
+    info!("Running the self-test with a {} map at {}", format_size(size as u64), path.display());
+
+    let start = Instant::now();
+    let mut mapping = match create_file_mapping(
+        &path,
+        &name,
+        size,
+        Protection::ReadWrite,
+        DEFAULT_FILE_ATTRIBUTES,
+        policy,
+    ) {
+        Ok((mapping, _)) => mapping,
+        Err(e) => {
+            let _ = remove_file(&path);
+            return Err(e.context("The self-test couldn't create its map"));
+        }
+    };
+    // The file is removed once the mapping is dropped, even if a later phase fails.
+    mapping.own_file(&path);
+    let create = start.elapsed();
+
+    let start = Instant::now();
+    mapping.zero().context("The self-test couldn't fill its map")?;
+    let fill = start.elapsed();
+
+    let start = Instant::now();
+    let content = mapping.view().context("The self-test couldn't read its map")?.to_vec();
+    let read = start.elapsed();
+
+    if let Some(offset) = content.iter().position(|&byte| byte != 0) {
+        bail!("The self-test read back a byte which isn't zero at offset {offset:#x}");
+    }
+
+    let start = Instant::now();
+    mapping.release_file();
+    drop(mapping);
+    remove_file(&path)
+        .with_context(|| format!("The self-test couldn't remove {}", path.display()))?;
+    let remove = start.elapsed();
+
+    let throughput =
+        |duration: Duration| size as f64 / MIB / duration.as_secs_f64().max(f64::EPSILON);
+
+    println!("create {:.3} ms", create.as_secs_f64() * 1000.0);
+    println!("fill {:.3} ms {:.1} MiB/s", fill.as_secs_f64() * 1000.0, throughput(fill));
+    println!("read {:.3} ms {:.1} MiB/s", read.as_secs_f64() * 1000.0, throughput(read));
+    println!("remove {:.3} ms", remove.as_secs_f64() * 1000.0);
+
+    Ok(())
+}
+
+/// Format the given bytes as a hex dump, every line contains the offset, the
+/// bytes in hexadecimal, and the bytes as ASCII characters.
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+
+    for (line, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        write!(dump, "{:08x} ", line * BYTES_PER_LINE).expect("Writing to a String never fails");
+
+        for index in 0..BYTES_PER_LINE {
+            match chunk.get(index) {
+                Some(byte) => write!(dump, " {byte:02x}"),
+                None => write!(dump, "   "),
+            }
+            .expect("Writing to a String never fails");
+        }
+
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+
+        writeln!(dump, "  |{ascii}|").expect("Writing to a String never fails");
+    }
+
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_line() {
+        assert_eq!(
+            hex_dump(b"0123456789abcdef"),
+            "00000000  30 31 32 33 34 35 36 37 38 39 61 62 63 64 65 66  |0123456789abcdef|\n"
+        );
+    }
+
+    #[test]
+    fn short_last_line_is_padded() {
+        assert_eq!(
+            hex_dump(b"0123456789abcdefA"),
+            "00000000  30 31 32 33 34 35 36 37 38 39 61 62 63 64 65 66  |0123456789abcdef|\n\
+             00000010  41                                               |A|\n"
+        );
+    }
+
+    #[test]
+    fn non_graphic_bytes_are_dots() {
+        assert_eq!(
+            hex_dump(&[0x00, b'a', 0x7f, b' ', 0xff, b'\n']),
+            "00000000  00 61 7f 20 ff 0a                                |.a. ..|\n"
+        );
+    }
+}
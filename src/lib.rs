@@ -0,0 +1,74 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Create named shared memory mappings under Wine/Proton which are backed by
+//! files in `/dev/shm`, making the shared memory of Windows applications
+//! accessible to Linux applications.
+//!
+//! ```no_run
+//! use shm_bridge::{find_shm_dir, Bridge};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let shm_dir = find_shm_dir(4096);
+//!
+//! let mut bridge = Bridge::new(shm_dir.path);
+//! bridge.add_map("acpmf_static", 2048).add_map("acpmf_physics", 2048);
+//! bridge.start()?;
+//!
+//! // The simulator can now open the mappings, while Linux applications read
+//! // the files in `/dev/shm`.
+//!
+//! bridge.cleanup().into_result()?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod adoption;
+pub mod bridge;
+pub mod config;
+pub mod consumers;
+pub mod error;
+pub mod file_mapping;
+pub mod header;
+pub mod hooks;
+pub mod inspect;
+pub mod lock;
+pub mod map_spec;
+mod mounts;
+pub mod output;
+pub mod pattern;
+pub mod pid_file;
+pub mod presets;
+pub mod progress;
+pub mod retry;
+pub mod shm_dir;
+pub mod shutdown;
+pub mod size;
+pub mod status_file;
+pub mod unix;
+pub mod wine;
+
+pub use bridge::{
+    attach_file_mapping, clean_up, clean_up_summary, create_file_mapping, Bridge, MapStatus,
+};
+pub use error::Error as BridgeError;
+pub use file_mapping::FileMapping;
+pub use map_spec::MapSpec;
+pub use shm_dir::{find_shm_dir, ShmDir};
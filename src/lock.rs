@@ -0,0 +1,104 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::{
+    fs::{remove_file, File},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use log::info;
+
+/// A lock file which ensures that only a single bridge instance manages a map.
+///
+/// The lock file is placed next to the `/dev/shm` backed file of the map, i.e.
+/// `/dev/shm/acpmf_static.lock`, and contains the process ID of the instance
+/// holding the lock. The lock is released, i.e. the lock file removed, when
+/// the [`MapLock`] is dropped.
+pub struct MapLock {
+    path: PathBuf,
+}
+
+impl MapLock {
+    /// Acquire the lock for the map with the given name.
+    ///
+    /// Fails if another instance already holds the lock.
+    pub fn acquire(dir: &Path, name: &str) -> Result<Self> {
+        let path = lock_path(dir, name);
+
+        let mut file = match File::options().write(true).create_new(true).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                let pid = std::fs::read_to_string(&path).unwrap_or_default();
+                let pid = if pid.trim().is_empty() { "unknown" } else { pid.trim() };
+
+                bail!(
+                    "The map {name} is already managed by another instance of the bridge with the \
+                     PID {pid}. If that instance isn't running anymore, remove the stale lock \
+                     file {} using the clean subcommand",
+                    path.display()
+                );
+            }
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Could not create the lock file {}", path.display()))
+            }
+        };
+
+        let lock = MapLock { path };
+
+        // This is the Wine process ID, not the one the Linux side sees.
+        write!(file, "{}", std::process::id())
+            .with_context(|| format!("Could not write the lock file {}", lock.path.display()))?;
+
+        Ok(lock)
+    }
+}
+
+impl Drop for MapLock {
+    fn drop(&mut self) {
+        // There's not much we can do if an error happens here, so let's ignore it.
+        let _ = remove_file(&self.path);
+    }
+}
+
+/// Remove the lock file of the given map, if one exists.
+///
+/// This is meant to remove stale lock files a killed bridge instance left
+/// behind.
+pub fn remove_stale_lock(dir: &Path, name: &str) -> Result<()> {
+    let path = lock_path(dir, name);
+
+    match remove_file(&path) {
+        Ok(()) => {
+            info!("Removed the stale lock file {}", path.display());
+            Ok(())
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => {
+            Err(e).with_context(|| format!("Could not remove the lock file {}", path.display()))
+        }
+    }
+}
+
+fn lock_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{name}.lock"))
+}
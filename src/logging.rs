@@ -0,0 +1,124 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::{
+    fmt::Display,
+    io::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use clap::ValueEnum;
+use log::{Level, LevelFilter};
+use shm_bridge::progress;
+
+/// The log target of informational messages which report a success, they are
+/// printed in green, i.e. `info!(target: SUCCESS, "...")`.
+pub const SUCCESS: &str = "shm_bridge::success";
+
+/// Should our messages be colored.
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// When the messages printed to stderr should be colored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color the messages if stderr is a terminal.
+    #[default]
+    Auto,
+    /// Always color the messages.
+    Always,
+    /// Never color the messages.
+    Never,
+}
+
+impl ColorChoice {
+    /// Should the messages be colored, given whether stderr is a terminal.
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => is_terminal,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// The colors our messages use.
+#[derive(Debug, Clone, Copy)]
+pub enum Color {
+    Red,
+    Yellow,
+    Green,
+}
+
+/// Wrap the text into the ANSI escape codes of the given color, if coloring
+/// was enabled in [`init()`].
+pub fn paint(color: Color, text: impl Display) -> String {
+    if !COLOR.load(Ordering::SeqCst) {
+        return text.to_string();
+    }
+
+    let code = match color {
+        Color::Red => 31,
+        Color::Yellow => 33,
+        Color::Green => 32,
+    };
+
+    format!("\x1b[{code}m{text}\x1b[0m")
+}
+
+/// Set up the logger which prints our status messages to stderr.
+///
+/// By default informational messages are printed, every `verbose` level
+/// enables a more detailed level, while `quiet` restricts the output to
+/// warnings and errors. The `RUST_LOG` environment variable can be used to
+/// override the level. With `color`, warnings are printed in yellow, errors in
+/// red, and messages using the [`SUCCESS`] target in green.
+pub fn init(verbose: u8, quiet: bool, color: bool) {
+    COLOR.store(color, Ordering::SeqCst);
+
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .format(|buf, record| {
+            // The progress line is redrawn with the next update, below this message.
+            progress::clear();
+
+            match record.level() {
+                Level::Info if record.target() == SUCCESS => {
+                    writeln!(buf, "{}", paint(Color::Green, record.args()))
+                }
+                Level::Info => writeln!(buf, "{}", record.args()),
+                Level::Warn => {
+                    writeln!(buf, "{}", paint(Color::Yellow, format!("Warning: {}", record.args())))
+                }
+                Level::Error => {
+                    writeln!(buf, "{}", paint(Color::Red, format!("Error: {}", record.args())))
+                }
+                level => writeln!(buf, "{level}: {}", record.args()),
+            }
+        })
+        .init();
+}
@@ -20,158 +20,1468 @@
 // SOFTWARE.
 
 use std::{
-    fs::{remove_file, File},
-    os::windows::fs::OpenOptionsExt,
-    path::{Path, PathBuf},
+    fs::remove_file,
+    io::{stderr, IsTerminal},
+    path::Path,
+    process::ExitCode,
+    time::{Duration, Instant, SystemTime},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use clap::Parser;
-use windows::Win32::Storage::FileSystem::FILE_ATTRIBUTE_TEMPORARY;
+use log::{debug, info, warn};
+use shm_bridge::{
+    adoption,
+    bridge::{self, clean_up_aliases, clean_up_summary, CleanUpSummary, MapStatus},
+    config::{find_default_config, load_config},
+    consumers,
+    error::{exit_code, Error},
+    file_mapping::{allocation_granularity, existing_size, page_size},
+    header::{decode_size, Endianness, SizeFrom, SizeWidth},
+    hooks, inspect,
+    lock::{remove_stale_lock, MapLock},
+    map_spec::{self, pair_maps, parse_map_lines, parse_map_list, Access, MapSpec},
+    output::{self, Event, OutputFormat},
+    pattern,
+    pid_file::{PidFile, PidFileGuard},
+    presets::Preset,
+    progress,
+    retry::RetryPolicy,
+    shm_dir::{
+        self, check_free_space, check_tmpfs, find_hugetlbfs, find_shm_dir, find_shm_dir_auto,
+        print_mounts, space_usage, ShmDir,
+    },
+    shutdown::{self, Shutdown},
+    size::{format_size, format_size_with},
+    status_file::{self, StatusFile},
+    unix, wine, Bridge, FileMapping,
+};
+
+use crate::{
+    cli::{
+        CheckArgs, CleanArgs, Cli, Command, CreateArgs, DumpArgs, GlobalArgs, InfoArgs, ListArgs,
+        MapArgs, SelftestArgs,
+    },
+    logging::Color,
+};
 
-use crate::file_mapping::FileMapping;
+mod cli;
+mod logging;
+mod version;
 
-mod file_mapping;
+/// The environment variable which can contain the maps, i.e. `foo:1M,bar:2M`,
+/// if none were given on the command line.
+const MAPS_ENV_VAR: &str = "SHM_BRIDGE_MAPS";
 
-const LONG_ABOUT: &str = "Shared Memory Bridge facilitates sharing memory between Windows\n\
-                          applications running under Wine/Proton and Linux, offering a seamless\n\
-                          way to access and manipulate named shared memory spaces across these\n\
-                          platforms. It's particularly useful in gaming and simulations, allowing\n\
-                          Linux applications to directly read data from Windows applications.\n\n\
-                          Example Usage:\n\n\
-                          To launch the bridge and view command line options, use the following \
-                          command:\n    \
-                              protontricks-launch --appid APPID shm-bridge.exe\n\n\
-                          This will display help output and available options for `shm-bridge`,\n\
-                          guiding you through the necessary steps to set up and run the bridge\n\
-                          within your specific environment.";
+/// How long we wait before the first restart with `--restart-on-crash`, the
+/// delay is doubled for every following restart.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
 
-// TODO: Support something besides Assetto Corsa.
-/// The list of shared memory mappings AC/ACC create.
-const ACC_FILES: &[&str] = &["acpmf_crewchief", "acpmf_static", "acpmf_physics", "acpmf_graphics"];
+/// The longest we wait before a restart with `--restart-on-crash`.
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
 
-#[derive(Parser)]
-#[command(author, version, about, long_about = LONG_ABOUT)]
-struct Cli {}
+/// Check that every map name appears only once in the given list of maps.
+///
+/// If `dedupe` is set, duplicate maps are replaced by their last definition
+/// instead of returning an error.
+fn check_duplicates(maps: Vec<MapSpec>, dedupe: bool) -> Result<Vec<MapSpec>> {
+    let mut checked: Vec<MapSpec> = Vec::with_capacity(maps.len());
 
-// TODO: Should we use the real structs from simetry for this? Seems a bit
-// overkill.
-fn file_size(name: &str) -> usize {
-    match name {
-        "acpmf_crewchief" => 15660,
-        _ => 2048,
+    for map in maps {
+        match checked.iter_mut().find(|existing| existing.name == map.name) {
+            Some(existing) if dedupe => {
+                warn!(
+                    "The map {} was given multiple times, using the size {} instead of {}",
+                    map.name, map.size, existing.size
+                );
+                *existing = map;
+            }
+            Some(existing) => {
+                return Err(Error::DuplicateMap {
+                    name: map.name,
+                    first_size: existing.size,
+                    second_size: map.size,
+                }
+                .into());
+            }
+            None => checked.push(map),
+        }
     }
+
+    Ok(checked)
 }
 
-fn find_shm_dir() -> PathBuf {
-    // TODO: Support non-standard tmpfs mount points. This can be achieved by
-    // parsing `/proc/mounts`, or if that's not available, by parsing `/etc/fstab`.
+/// Read the maps from the [`MAPS_ENV_VAR`] environment variable, if it's set.
+fn env_maps(dedupe: bool, si_units: bool) -> Result<Option<Vec<MapSpec>>> {
+    let Ok(value) = std::env::var(MAPS_ENV_VAR) else {
+        return Ok(None);
+    };
 
-    /// The default path for our tmpfs.
-    const TMPFS_PATH: &str = "/dev/shm/";
+    let maps = parse_map_list(&value, si_units)
+        .map_err(|e| anyhow!(e))
+        .with_context(|| format!("Could not parse the maps in the {MAPS_ENV_VAR} variable"))?;
 
-    // TODO: We should also check that /dev/shm, or any other filesystem we found
-    // using `/proc/mounts` is actually a `tmpfs`. This is sadly problematic, I
-    // tried to use `GetVolumeInformationW` but, as the name suggest, it expects
-    // a volume, so `C:\\`, or as Wine exposes `/`, `Z:\\`. We can't check the
-    // file system name of `Z:\\dev\shm` for example. Even if we do check the
-    // filesystem name of `Z:\\` we get `NTFS` back.
+    info!("Using the maps from the {MAPS_ENV_VAR} environment variable");
 
-    PathBuf::from(TMPFS_PATH)
+    Ok(Some(check_duplicates(maps, dedupe)?))
 }
 
-fn create_file_mapping(dir: &Path, file_name: &str, size: usize) -> Result<FileMapping> {
-    let path = dir.join(file_name);
+/// Read the maps given to `--from-stdin`.
+fn stdin_maps(si_units: bool) -> Result<Vec<MapSpec>> {
+    let input =
+        std::io::read_to_string(std::io::stdin()).context("Could not read the maps from stdin")?;
 
-    // First we create a /dev/shm backed file.
-    //
-    // Now hear me out, usually we should use `shm_open(3)` here, but on Linux
-    // `shm_open()` just calls `open()`. It does have some logic to find the
-    // tmpfs location if it's mounted in a non-standard location. Since we can't
-    // call `shm_open(3)` from inside the Wine environment
-    let file = File::options()
-        .read(true)
-        .write(true)
-        .attributes(FILE_ATTRIBUTE_TEMPORARY.0)
-        .create(true)
-        .open(&path)
-        .context(format!("Could not open the tmpfs file: {path:?}"))?;
-
-    // Now we create a mapping that is backed by the previously created /dev/shm`
-    // file.
-    let mapping = FileMapping::new(
-        // We're going to use the same names the Simulator uses. This ensures that the
-        // simulator will reuse this `/dev/shm` backed mapping instead of creating a new anonymous
-        // one. Making the simulator reuse the mapping in turn means that the telemetry data will
-        // be available in `/dev/shm` as well, making it accessible to Linux.
-        file_name,
-        // Pass in the handle of the `/dev/shm` file, this ensures that the file mapping is a file
-        // backed one and is using our tmpfs file created on the Linux side.
-        &file,
-        // The documentation[1] for CreateFileMapping states that the sizes are only necessary if
-        // we're using a `INVALID_HANDLE_VALUE` for the file handle.
-        //
-        // It also states the following:
-        // > If this parameter and dwMaximumSizeHigh are 0 (zero), the maximum size of the
-        // > file mapping object is equal to the current size of the file that hFile identifies.
-        //
-        // This sadly doesn't seem to work with our `/dev/shm` file and makes the Simulator crash,
-        // so we're passing the sizes manually.
-        //
-        // [1]: https://learn.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-createfilemappinga#parameters
-        size,
-    )?;
-
-    // Return the mapping, the caller needs to ensure that the mapping object stays
-    // alive. On the other hand, the `/dev/shm` backed file can be closed.
-    Ok(mapping)
-}
-
-fn main() -> Result<()> {
-    let _ = Cli::parse();
-
-    let mut mappings = Vec::new();
-
-    // Find a suitable tmpfs based mountpoint, this is usually `/dev/shm`.
-    let shm_dir = find_shm_dir();
-
-    println!("Found a tmpfs filesystem at {}", shm_dir.to_string_lossy());
-
-    for file_name in ACC_FILES {
-        let size = file_size(file_name);
-        let mapping = create_file_mapping(&shm_dir, file_name, size)
-            .with_context(|| format!("Error creating a file mapping for {file_name}"))?;
-
-        println!("Created a tmpfs backed mapping for {file_name} with size {size}");
-        mappings.push(mapping);
-    }
-
-    let current_thread = std::thread::current();
-
-    // Set a CTRL_C_EVENT/CTRL_BREAK_EVENT handler which will unpark our thread and
-    // let main finish.
-    ctrlc::set_handler(move || {
-        current_thread.unpark();
-    })
-    .expect("We should be able to set up a CTRL-C handler.");
-
-    println!("All mappings were successfully created, press CTRL-C to exit.");
-
-    // Park the main thread so we don't exit and don't drop the `FileMapping`
-    // objects.
-    std::thread::park();
-
-    println!("\nShutting down.");
-
-    // The CTRL-C handler has unparked us, somebody wants us to stop running so
-    // let's unlink the `/dev/shm` files.
-    for file_name in ACC_FILES {
-        println!("Removing mapping {file_name}");
-        let path = shm_dir.join(file_name);
+    let maps = parse_map_lines(&input, si_units)
+        .map_err(|e| anyhow!(e))
+        .context("Could not parse the maps read from stdin")?;
+
+    info!("Read {} maps from stdin", maps.len());
+
+    Ok(maps)
+}
+
+/// Collect the maps the bridge should manage from the preset, the config file,
+/// and the command line.
+///
+/// Maps given on the command line replace maps with the same name from the
+/// config file, which in turn replace maps from the preset. A map name may only
+/// be given once on the command line and once in the config file. Maps read
+/// using `--from-stdin` are treated like maps from a config file. If neither
+/// maps, a config file, nor a preset were given, the maps in the
+/// [`MAPS_ENV_VAR`] environment variable are used, or the maps of the default
+/// config file, see [`find_default_config()`]. If no maps were given at all,
+/// the maps of the [`Preset::Acc`] preset are returned.
+///
+/// The suffixes of the sizes use decimal multipliers if `si_units` is set.
+fn collect_maps(args: &MapArgs, si_units: bool) -> Result<Vec<MapSpec>> {
+    collect_maps_with(args, si_units, Vec::new())
+}
+
+/// Collect the maps like [`collect_maps()`] does, the `extra` maps are treated
+/// like maps given on the command line.
+fn collect_maps_with(args: &MapArgs, si_units: bool, extra: Vec<MapSpec>) -> Result<Vec<MapSpec>> {
+    let mut maps = args.preset.map(Preset::maps).unwrap_or_default();
+
+    let config_maps = match &args.config {
+        Some(path) => {
+            check_duplicates(load_config(path, args.config_format, si_units)?.maps, args.dedupe)?
+        }
+        None if args.from_stdin => check_duplicates(stdin_maps(si_units)?, args.dedupe)?,
+        None => Vec::new(),
+    };
+    let mut cli_maps = pair_maps(&args.map, &args.size, args.size_all.as_ref(), si_units)?;
+    cli_maps.extend(extra);
+    let cli_maps = check_duplicates(cli_maps, args.dedupe)?;
+
+    // The environment variable is only a fallback, any map given on the command
+    // line takes precedence.
+    if cli_maps.is_empty() && args.config.is_none() && !args.from_stdin && args.preset.is_none() {
+        if let Some(env_maps) = env_maps(args.dedupe, si_units)? {
+            maps = env_maps;
+        }
+    }
+
+    for map in config_maps.into_iter().chain(cli_maps) {
+        match maps.iter_mut().find(|existing| existing.name == map.name) {
+            Some(existing) => *existing = map,
+            None => maps.push(map),
+        }
+    }
+
+    if maps.is_empty() {
+        if let Some(path) = find_default_config() {
+            info!("Using the maps from the config file {}", path.display());
+            maps = check_duplicates(load_config(&path, None, si_units)?.maps, args.dedupe)?;
+        }
+    }
+
+    if maps.is_empty() {
+        maps = Preset::Acc.maps();
+    }
+
+    for map in &maps {
+        map.validate_name().map_err(|reason| Error::InvalidMapName { reason })?;
+    }
+
+    Ok(maps)
+}
+
+/// Remove the `/dev/shm` backed files, the aliases, and the lock files a killed
+/// bridge instance left behind.
+fn clean_up_stale(
+    dir: &Path,
+    prefix: &str,
+    maps: &[MapSpec],
+    aliases: &[(String, String)],
+    policy: RetryPolicy,
+) -> Result<()> {
+    for map in maps {
+        remove_stale_lock(dir, &map.file_name(prefix))?;
+    }
+
+    let mut summary = clean_up_summary(dir, prefix, maps, policy);
+    summary.failed.extend(clean_up_aliases(dir, aliases));
+
+    if summary.is_clean() {
+        info!(target: logging::SUCCESS, "Clean up finished, {summary}");
+    } else {
+        warn!("Clean up incomplete, {summary}");
+    }
+
+    report_clean_up(summary)
+}
+
+/// Emit the summary of a clean up and turn it into the result the bridge exits
+/// with.
+fn report_clean_up(summary: CleanUpSummary) -> Result<()> {
+    output::emit(&Event::CleanUpSummary {
+        removed: summary.removed.len(),
+        absent: summary.absent.len(),
+        failed: summary.failed.len(),
+    });
+
+    Ok(summary.into_result()?)
+}
+
+/// Clean up the maps recorded in the given PID file and remove the PID file.
+fn clean_up_from_pid_file(global: &GlobalArgs, path: &Path, dry_run: bool) -> Result<()> {
+    let pid_file = PidFile::read(path)?;
+
+    // The PID file knows the prefix, giving a different one is most likely a
+    // mix-up of the PID files.
+    if global.prefix.as_ref().is_some_and(|prefix| *prefix != pid_file.prefix) {
+        bail!(
+            "The PID file {} was written by a bridge using the prefix `{}`, which doesn't match \
+             the prefix `{}` given to --prefix",
+            path.display(),
+            pid_file.prefix,
+            global.file_prefix()
+        );
+    }
+
+    if pid_file.is_process_alive() {
+        warn!(
+            "The bridge with the PID {} which wrote the PID file seems to be still \
+             running",
+            pid_file.pid
+        );
+    }
+
+    if dry_run {
+        for map in &pid_file.maps {
+            println!("remove {}", pid_file.dir.join(map.file_name(&pid_file.prefix)).display());
+        }
+
+        return Ok(());
+    }
+
+    clean_up_stale(&pid_file.dir, &pid_file.prefix, &pid_file.maps, &[], global.retry_policy())?;
+
+    remove_file(path).with_context(|| format!("Could not remove the PID file {}", path.display()))
+}
+
+/// Get the shm directory existing mappings should be looked up in.
+fn existing_shm_dir(global: &GlobalArgs) -> Result<ShmDir> {
+    if global.verbose_mounts {
+        print_mounts(0, global.shm_dir.as_deref());
+    }
+
+    select_shm_dir(global, 0)
+}
+
+/// Select the shm directory based on the `--shm-dir` argument.
+fn select_shm_dir(global: &GlobalArgs, required_space: u64) -> Result<ShmDir> {
+    match &global.shm_dir {
+        Some(path) if shm_dir::is_auto(path) => Ok(find_shm_dir_auto(required_space)),
+        Some(path) => ShmDir::from_path(path),
+        None => Ok(find_shm_dir(required_space)),
+    }
+}
+
+/// Get the shm directory the given maps should be placed in.
+fn shm_dir_for_maps(global: &GlobalArgs, maps: &[MapSpec]) -> Result<ShmDir> {
+    let required_space = maps.iter().map(|map| map.size as u64).sum();
+
+    if global.verbose_mounts {
+        print_mounts(required_space, global.shm_dir.as_deref());
+    }
+
+    // Find a suitable tmpfs based mountpoint, this is usually `/dev/shm`, unless
+    // the user told us where to put the files.
+    let shm_dir = select_shm_dir(global, required_space)?;
+
+    match &shm_dir.fs_type {
+        Some(fs_type) => info!("Found a {fs_type} filesystem at {}", shm_dir.path.display()),
+        None => info!("Using {}, its filesystem type is unknown", shm_dir.path.display()),
+    }
+
+    Ok(shm_dir)
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", logging::paint(Color::Red, format!("Error: {e:?}")));
+            output::emit(&Event::Error { message: format!("{e:#}") });
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    let cli = Cli::parse();
+
+    if cli.version_json {
+        version::print_json();
+        return Ok(());
+    }
+
+    let (global, command) = cli.into_parts();
+
+    let color = !global.quiet
+        && global.output != OutputFormat::Json
+        && global.color.enabled(stderr().is_terminal());
+
+    logging::init(global.verbose, global.quiet, color);
+    progress::set_enabled(!global.quiet && stderr().is_terminal());
+    output::init(global.output);
+
+    let wine_version = wine::ensure_wine()?;
+    debug!("Running under Wine {wine_version}");
+
+    match command {
+        Command::Create(args) => create(&global, &args),
+        Command::Clean(args) => clean(&global, &args),
+        Command::Dump(args) => dump(&global, &args),
+        Command::List(args) => list(&global, &args),
+        Command::Check(args) => check(&global, &args),
+        Command::Info(args) => info(&global, &args),
+        Command::Selftest(args) => selftest(&global, &args),
+    }
+}
+
+/// Run the `check` subcommand.
+fn check(global: &GlobalArgs, args: &CheckArgs) -> Result<()> {
+    let maps = collect_maps(&args.maps, global.si_sizes)?;
+    let shm_dir = existing_shm_dir(global)?;
+
+    inspect::check(
+        &shm_dir.path,
+        global.file_prefix(),
+        &maps,
+        args.maps.padding.resolve(global.si_sizes),
+    )
+}
+
+/// Run the `info` subcommand.
+fn info(global: &GlobalArgs, args: &InfoArgs) -> Result<()> {
+    let maps = collect_maps(&args.maps, global.si_sizes)?;
+    inspect::info(&maps, args.maps.padding.resolve(global.si_sizes));
+
+    Ok(())
+}
+
+/// Run the `selftest` subcommand.
+fn selftest(global: &GlobalArgs, args: &SelftestArgs) -> Result<()> {
+    let shm_dir = select_shm_dir(global, args.size as u64)?;
+    check_tmpfs(&shm_dir, true)?;
+
+    inspect::selftest(&shm_dir.path, args.size, global.retry_policy())
+}
+
+/// Run the `list` subcommand.
+fn list(global: &GlobalArgs, args: &ListArgs) -> Result<()> {
+    let shm_dir = existing_shm_dir(global)?;
+    let maps = args
+        .config
+        .as_deref()
+        .map(|path| load_config(path, args.config_format, global.si_sizes))
+        .transpose()?
+        .map(|config| config.maps);
+
+    inspect::list(&shm_dir, global.file_prefix(), args.temporary, maps.as_deref())
+}
+
+/// Run the `dump` subcommand.
+fn dump(global: &GlobalArgs, args: &DumpArgs) -> Result<()> {
+    let shm_dir = existing_shm_dir(global)?;
+    let prefix = global.file_prefix();
+
+    match &args.out {
+        Some(out) => inspect::snapshot(&shm_dir.path, prefix, &args.name, args.bytes, out),
+        None => {
+            let interval = Duration::try_from_secs_f64(args.interval)
+                .context("The interval needs to be a positive number of seconds")?;
+
+            inspect::dump(&shm_dir.path, prefix, &args.name, args.bytes, interval)
+        }
+    }
+}
+
+/// Run the `clean` subcommand.
+fn clean(global: &GlobalArgs, args: &CleanArgs) -> Result<()> {
+    if let Some(path) = &args.from_pid_file {
+        return clean_up_from_pid_file(global, path, args.dry_run);
+    }
+
+    if let Some(pattern) = args.glob.iter().find(|pattern| pattern::matches_everything(pattern)) {
+        if !args.force {
+            bail!(
+                "The pattern {pattern} given to --glob matches every file in the shm directory, \
+                 use --force if this is really what you want"
+            );
+        }
+    }
+
+    // Without any maps, the default preset would be cleaned up as well, which
+    // isn't what somebody looking for files by a pattern expects.
+    let explicit_maps = !args.maps.map.is_empty()
+        || args.maps.config.is_some()
+        || args.maps.from_stdin
+        || args.maps.preset.is_some();
+
+    let maps = if args.glob.is_empty() || explicit_maps {
+        collect_maps(&args.maps, global.si_sizes)?
+    } else {
+        Vec::new()
+    };
+    let shm_dir = shm_dir_for_maps(global, &maps)?;
+    let prefix = global.file_prefix();
+
+    // The files of the given maps are removed anyway.
+    let map_paths: Vec<_> =
+        maps.iter().map(|map| shm_dir.path.join(map.file_name(prefix))).collect();
+    let globbed: Vec<_> = if args.glob.is_empty() {
+        Vec::new()
+    } else {
+        inspect::temporary_matching(&shm_dir.path, &args.glob)?
+            .into_iter()
+            .filter(|(path, _)| !map_paths.contains(path))
+            .collect()
+    };
+
+    let orphans = match &args.maps.config {
+        Some(path) if args.prune => {
+            let patterns = load_config(path, args.maps.config_format, global.si_sizes)?.prune;
+
+            if patterns.is_empty() {
+                bail!("The config file {} doesn't contain any prune patterns", path.display());
+            }
+
+            inspect::orphans(&shm_dir.path, prefix, &maps, &patterns)?
+        }
+        _ => Vec::new(),
+    };
+
+    if args.dry_run {
+        let reason = if args.prune { " listed" } else { "" };
+
+        for map in &maps {
+            println!("remove {}{reason}", shm_dir.path.join(map.file_name(prefix)).display());
+        }
+
+        for (_, alias) in &args.alias {
+            println!("remove {}{reason}", shm_dir.path.join(alias).display());
+        }
+
+        for (path, _) in &orphans {
+            println!("remove {} orphan", path.display());
+        }
+
+        for (path, _) in &globbed {
+            println!("remove {} glob", path.display());
+        }
+
+        return Ok(());
+    }
+
+    clean_up_stale(&shm_dir.path, prefix, &maps, &args.alias, global.retry_policy())?;
+
+    for (path, pattern) in orphans {
+        info!("Removing the orphaned file {}, it matches the pattern {pattern}", path.display());
 
         remove_file(&path)
-            .with_context(|| format!("Could not unlink the /dev/shm backed file {file_name}"))?;
+            .with_context(|| format!("Could not remove the orphaned file {}", path.display()))?;
+    }
+
+    if !args.glob.is_empty() && globbed.is_empty() {
+        info!("No file created by the bridge matches the patterns given to --glob");
+    }
+
+    for (path, pattern) in globbed {
+        info!("Removing the file {}, it matches the pattern {pattern}", path.display());
+
+        remove_file(&path)
+            .with_context(|| format!("Could not remove the file {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Tell the user where the `/dev/shm` backed files, using the given prefix, can
+/// be found, from inside Wine as well as from Linux.
+fn print_paths(shm_dir: &ShmDir, prefix: &str, maps: &[MapSpec]) {
+    let wine_dir = shm_dir.wine_path().to_string_lossy().replace('/', "\\");
+    let linux_dir = shm_dir.linux_path();
+
+    info!("The mappings are backed by the following files:");
+
+    for map in maps {
+        let name = &map.name;
+        let file_name = map.file_name(prefix);
+
+        match &linux_dir {
+            Some(linux_dir) => {
+                info!(
+                    "    {name}: {linux_dir}/{file_name} on Linux, {wine_dir}\\{file_name} in Wine"
+                )
+            }
+            None => warn!(
+                "    {name}: {wine_dir}\\{file_name} in Wine, the Linux path is unknown since the \
+                 directory isn't on the Z: drive"
+            ),
+        }
+    }
+}
+
+/// Print a table containing the space every map takes up, the running total,
+/// and the space that is available in the shm directory.
+fn print_space_report(shm_dir: &ShmDir, prefix: &str, maps: &[MapSpec]) -> Result<()> {
+    let Some(usage) = space_usage(shm_dir, prefix, maps)? else {
+        bail!("The free space of {} is unknown", shm_dir.path.display());
+    };
+
+    let overflow = usage.first_overflow();
+    let width = maps.iter().map(|map| map.name.len()).max().unwrap_or_default().max(3);
+
+    println!("{:<width$}  {:>12}  {:>12}  {:>12}", "MAP", "SIZE", "FILE SIZE", "TOTAL");
+
+    let mut total = 0;
+
+    for (index, (map, required)) in maps.iter().zip(&usage.required).enumerate() {
+        total += required;
+        let marker = if overflow == Some(index) { "  <- doesn't fit" } else { "" };
+
+        println!("{:<width$}  {:>12}  {required:>12}  {total:>12}{marker}", map.name, map.size);
+    }
+
+    println!("{:<width$}  {:>12}  {:>12}  {:>12}", "available", "", "", usage.available);
+
+    Ok(())
+}
+
+/// Tell the user how the `/dev/shm` backed files we're leaving behind can be
+/// removed.
+fn print_clean_command(
+    shm_dir: &ShmDir,
+    prefix: &str,
+    maps: &[MapSpec],
+    aliases: &[(String, String)],
+) {
+    info!(
+        "Keeping the /dev/shm backed files, remove them using:\n    {}",
+        clean_command(shm_dir, prefix, maps, aliases)
+    );
+}
+
+/// Get the `clean` command which removes the `/dev/shm` backed files of the
+/// given maps, using the given prefix.
+fn clean_command(
+    shm_dir: &ShmDir,
+    prefix: &str,
+    maps: &[MapSpec],
+    aliases: &[(String, String)],
+) -> String {
+    let prefix = (!prefix.is_empty()).then(|| format!("--prefix {prefix}"));
+
+    let maps: Vec<_> = prefix
+        .into_iter()
+        .chain(maps.iter().map(|MapSpec { name, size, .. }| format!("--map {name}:{size}")))
+        .chain(aliases.iter().map(|(name, alias)| format!("--alias {name}={alias}")))
+        .collect();
+
+    format!("shm-bridge.exe clean --shm-dir {} {}", shm_dir.path.display(), maps.join(" "))
+}
+
+/// Check that none of the maps is larger than the given maximum size, which
+/// most likely would be a typo, i.e. `32G` instead of `32M`.
+///
+/// With `allow_huge` the large maps are only warned about.
+fn check_max_size(maps: &[MapSpec], max_size: usize, allow_huge: bool) -> Result<()> {
+    for MapSpec { name, size, .. } in maps.iter().filter(|map| map.size > max_size) {
+        if allow_huge {
+            warn!(
+                "The map {name} has a size of {size} bytes, which is larger than {max_size} bytes"
+            );
+        } else {
+            return Err(Error::MapTooLarge { name: name.clone(), size: *size, max_size }.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the size of the map given to `--size-from` from its source map.
+///
+/// The named mapping of the source map is used if it exists, otherwise the
+/// `/dev/shm` backed file of the source map is read.
+fn read_size_from(
+    global: &GlobalArgs,
+    size_from: &SizeFrom,
+    width: SizeWidth,
+    endianness: Endianness,
+) -> Result<MapSpec> {
+    let SizeFrom { name, source, offset } = size_from;
+    let end = offset.saturating_add(width.bytes());
+
+    let content = match FileMapping::open_named(source, end) {
+        Ok(mapping) => mapping.view()?.to_vec(),
+        Err(e) => {
+            debug!("Could not open the mapping {source}, reading its file instead: {e:#}");
+
+            let shm_dir = select_shm_dir(global, 0)?;
+            inspect::read_map(&shm_dir.path, global.file_prefix(), source)?
+        }
+    };
+
+    let size = decode_size(&content, *offset, width, endianness)
+        .map_err(|reason| anyhow!("Could not read the size of {name} from {source}: {reason}"))?;
+
+    info!("Read the size {size} of the map {name} from offset {offset} of {source}");
+
+    Ok(MapSpec::new(name.clone(), size))
+}
+
+/// Look up the size of the existing `/dev/shm` backed file of a map given to
+/// `--attach`.
+fn attached_map(dir: &Path, prefix: &str, name: &str) -> Result<MapSpec> {
+    let path = dir.join(map_spec::file_name(name, prefix));
+    let size = existing_size(&path)?
+        .ok_or_else(|| Error::MissingMap { name: name.to_owned(), path: path.clone() })
+        .context("Only existing files can be attached to, the file needs to be created first")?;
+
+    Ok(MapSpec::new(name, size as usize))
+}
+
+/// Round the sizes of the maps up, so the maps including their padding are a
+/// multiple of the given huge page size.
+///
+/// A map on a hugetlbfs always takes up whole huge pages, so the rounded up
+/// size is usable by the simulator as well.
+fn round_to_huge_pages(maps: &mut [MapSpec], page_size: usize, padding: usize) -> Result<()> {
+    for map in maps {
+        let rounded = map
+            .size
+            .checked_add(padding)
+            .and_then(|allocated| allocated.checked_next_multiple_of(page_size))
+            .with_context(|| format!("The map {} is too large to be rounded up", map.name))?
+            - padding;
+
+        if rounded != map.size {
+            debug!("Rounded the map {} up from {} to {rounded} bytes", map.name, map.size);
+            map.size = rounded;
+        }
     }
 
     Ok(())
 }
+
+/// How often the config file is checked for modifications with
+/// `--reload-config`.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reloads the `--config` file once its modification time changes.
+struct ConfigReload<'a> {
+    /// The arguments the maps were collected from.
+    args: &'a MapArgs,
+    /// The maps which were collected from other sources than the map arguments,
+    /// see [`collect_maps_with()`].
+    extra: Vec<MapSpec>,
+    /// The huge page size the maps are rounded up to, see
+    /// [`round_to_huge_pages()`].
+    huge_page_size: Option<usize>,
+    /// The padding of the maps.
+    padding: usize,
+    /// Do the suffixes of the sizes use decimal multipliers.
+    si_units: bool,
+    /// The prefix of the `/dev/shm` backed files.
+    prefix: &'a str,
+    /// The locks of the maps, as pairs of the map name and the lock, they're
+    /// taken and released together with the maps.
+    locks: Vec<(String, MapLock)>,
+    /// The PID file, it's rewritten whenever the maps change.
+    pid_file: Option<PidFileGuard>,
+    /// The modification time of the config file when it was last loaded.
+    modified: Option<SystemTime>,
+}
+
+impl<'a> ConfigReload<'a> {
+    fn new(
+        args: &'a MapArgs,
+        extra: Vec<MapSpec>,
+        huge_page_size: Option<usize>,
+        padding: usize,
+        si_units: bool,
+        prefix: &'a str,
+    ) -> Self {
+        let mut reload = Self {
+            args,
+            extra,
+            huge_page_size,
+            padding,
+            si_units,
+            prefix,
+            locks: Vec::new(),
+            pid_file: None,
+            modified: None,
+        };
+        reload.modified = reload.modification_time();
+
+        reload
+    }
+
+    fn modification_time(&self) -> Option<SystemTime> {
+        let path = self.args.config.as_deref()?;
+        std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    /// Reload the config file if it was modified since it was last loaded, and
+    /// bring the maps of the bridge in line with it.
+    ///
+    /// Maps which were added get created and maps which aren't listed anymore
+    /// get removed, maps whose size changed are only warned about, since
+    /// resizing them would pull the memory from under the simulator. Returns
+    /// `true` if the config file was reloaded.
+    fn check(&mut self, bridge: &mut Bridge) -> bool {
+        let modified = self.modification_time();
+
+        if modified == self.modified {
+            return false;
+        }
+
+        self.modified = modified;
+
+        info!("The config file was modified, reloading it");
+
+        let maps =
+            collect_maps_with(self.args, self.si_units, self.extra.clone()).and_then(|mut maps| {
+                if let Some(page_size) = self.huge_page_size {
+                    round_to_huge_pages(&mut maps, page_size, self.padding)?;
+                }
+
+                Ok(maps)
+            });
+
+        match maps {
+            Ok(maps) => self.reconcile(bridge, maps),
+            Err(e) => warn!("Could not reload the config file, keeping the current maps: {e:#}"),
+        }
+
+        true
+    }
+
+    /// Create and remove the mappings of the bridge, so it manages the given
+    /// maps.
+    ///
+    /// The lock of a map is taken before it's created and released once it's
+    /// removed, the PID file is rewritten afterwards.
+    fn reconcile(&mut self, bridge: &mut Bridge, maps: Vec<MapSpec>) {
+        let removed: Vec<_> = bridge
+            .maps()
+            .iter()
+            .filter(|existing| !maps.iter().any(|map| map.name == existing.name))
+            .map(|existing| existing.name.clone())
+            .collect();
+
+        for name in removed {
+            match bridge.stop_map(&name) {
+                Ok(summary) if summary.is_clean() => {
+                    info!("Removed the map {name} which isn't listed anymore, {summary}")
+                }
+                Ok(summary) => {
+                    warn!("Removed the map {name} with an incomplete clean up, {summary}")
+                }
+                Err(e) => warn!("Could not remove the map {name}: {e:#}"),
+            }
+
+            self.locks.retain(|(locked, _)| *locked != name);
+        }
+
+        for map in maps {
+            match bridge.maps().iter().find(|existing| existing.name == map.name) {
+                Some(existing) if existing.size != map.size => warn!(
+                    "The size of the map {} changed from {} to {} bytes, restart the bridge to \
+                     resize it",
+                    map.name, existing.size, map.size
+                ),
+                Some(_) => {}
+                None => {
+                    let name = map.name.clone();
+                    let lock = match MapLock::acquire(bridge.dir(), &map.file_name(self.prefix)) {
+                        Ok(lock) => lock,
+                        Err(e) => {
+                            warn!("Could not add the map {name}: {e:#}");
+                            continue;
+                        }
+                    };
+
+                    match bridge.start_map(map) {
+                        Ok(status) => {
+                            info!("Added the map {name} ({status})");
+                            self.locks.push((name, lock));
+                        }
+                        Err(e) => warn!("Could not add the map {name}: {e:#}"),
+                    }
+                }
+            }
+        }
+
+        if let Some(pid_file) = &mut self.pid_file {
+            // The files of attached maps belong to a Linux process.
+            let attached = |map: &MapSpec| {
+                bridge.statuses().contains(&(map.name.clone(), MapStatus::Attached))
+            };
+            let owned: Vec<_> =
+                bridge.maps().iter().filter(|map| !attached(map)).cloned().collect();
+
+            if let Err(e) = pid_file.update(&owned) {
+                warn!("{e:#}");
+            }
+        }
+    }
+}
+
+/// Park the current thread until a shutdown is requested or the timeout
+/// elapses.
+///
+/// If a `watch_interval` is given, the files of the maps are checked, and
+/// recreated if needed, using [`Bridge::repair()`] in that interval. If a
+/// `touch_interval` is given, the files are touched using [`Bridge::touch()`]
+/// in that interval. If a `reload` is given, the config file is checked for
+/// modifications every [`RELOAD_INTERVAL`]. The `status_file` is updated
+/// whenever the maps change.
+///
+/// Returns `true` if the timeout elapsed, or an error if some of the maps
+/// couldn't be repaired.
+fn park(
+    shutdown: &Shutdown,
+    bridge: &mut Bridge,
+    timeout: Option<Duration>,
+    watch_interval: Option<Duration>,
+    touch_interval: Option<Duration>,
+    mut reload: Option<&mut ConfigReload>,
+    mut status_file: Option<&mut StatusFile>,
+) -> Result<bool> {
+    let start = Instant::now();
+    let deadline = timeout.map(|timeout| start + timeout);
+    let mut next_watch = watch_interval.map(|interval| start + interval);
+    let mut next_touch = touch_interval.map(|interval| start + interval);
+    let mut next_reload = reload.is_some().then(|| start + RELOAD_INTERVAL);
+
+    loop {
+        let Some(next) =
+            [deadline, next_watch, next_touch, next_reload].into_iter().flatten().min()
+        else {
+            shutdown.wait();
+            return Ok(false);
+        };
+
+        if shutdown.wait_timeout(next.saturating_duration_since(Instant::now())) {
+            return Ok(false);
+        }
+
+        let now = Instant::now();
+
+        if deadline.is_some_and(|deadline| now >= deadline) {
+            return Ok(true);
+        }
+
+        let mut changed = false;
+
+        if let (Some(next), Some(interval)) = (next_watch, watch_interval) {
+            if now >= next {
+                let recreated = bridge.repair()?;
+
+                if recreated > 0 {
+                    info!("Recreated {recreated} of the mappings");
+                    changed = true;
+                }
+
+                next_watch = Some(now + interval);
+            }
+        }
+
+        if let (Some(next), Some(interval)) = (next_touch, touch_interval) {
+            if now >= next {
+                bridge.touch();
+                next_touch = Some(now + interval);
+            }
+        }
+
+        if let (Some(next), Some(reload)) = (next_reload, reload.as_deref_mut()) {
+            if now >= next {
+                changed |= reload.check(bridge);
+                next_reload = Some(now + RELOAD_INTERVAL);
+            }
+        }
+
+        if let Some(status_file) = status_file.as_deref_mut().filter(|_| changed) {
+            if let Err(e) = status_file.update(bridge) {
+                warn!("{e:#}");
+            }
+        }
+    }
+}
+
+/// Turn the maps which couldn't be created with `--best-effort` into the error
+/// the bridge exits with.
+fn creation_result(failures: Vec<(String, anyhow::Error)>) -> Result<()> {
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::CreateFailed { failures }.into())
+    }
+}
+
+/// Run the `create` subcommand.
+fn create(global: &GlobalArgs, args: &CreateArgs) -> Result<()> {
+    let si_units = global.si_sizes;
+    let padding = args.maps.padding.resolve(si_units);
+    let prefix = global.file_prefix();
+
+    let mut sized_maps = args
+        .size_from
+        .iter()
+        .map(|size_from| read_size_from(global, size_from, args.size_width, args.size_endianness))
+        .collect::<Result<Vec<_>>>()?;
+
+    if !args.attach.is_empty() {
+        let shm_dir = select_shm_dir(global, 0)?;
+
+        for name in &args.attach {
+            sized_maps.push(attached_map(&shm_dir.path, prefix, name)?);
+        }
+    }
+
+    if args.hugepages && global.shm_dir.is_some() {
+        bail!("--hugepages can't be combined with --shm-dir");
+    }
+
+    let huge_pages = args.hugepages.then(find_hugetlbfs).and_then(|huge_pages| {
+        if huge_pages.is_none() {
+            warn!("No hugetlbfs is mounted, backing the maps with regular pages");
+        }
+
+        huge_pages
+    });
+    let huge_page_size = huge_pages.as_ref().map(|huge_pages| huge_pages.page_size);
+
+    let mut reload = args.reload_config.then(|| {
+        ConfigReload::new(&args.maps, sized_maps.clone(), huge_page_size, padding, si_units, prefix)
+    });
+    let mut maps = collect_maps_with(&args.maps, si_units, sized_maps)?;
+
+    if let Some(page_size) = huge_page_size {
+        info!(
+            "Rounding the maps up to a multiple of the huge page size, {}",
+            format_size(page_size as u64)
+        );
+        round_to_huge_pages(&mut maps, page_size, padding)?;
+    }
+
+    let read_only: Vec<&String> = args
+        .read_only
+        .iter()
+        .chain(maps.iter().filter(|map| map.access == Access::ReadOnly).map(|map| &map.name))
+        .collect();
+
+    if let Some(preset) = args.maps.preset {
+        info!("Applied the {preset} preset, the following maps will be managed:");
+
+        for MapSpec { name, size, .. } in &maps {
+            info!("    {name} with size {size}");
+        }
+    }
+
+    let init_content = args
+        .init_from
+        .iter()
+        .map(|(name, path)| {
+            if !maps.iter().any(|map| &map.name == name) {
+                bail!("The map {name} given to --init-from isn't one of the maps to create");
+            }
+
+            if read_only.contains(&name) {
+                bail!("The read-only map {name} can't be initialized using --init-from");
+            }
+
+            let content = std::fs::read(path).with_context(|| {
+                format!("Could not read the initial content of {name} from {}", path.display())
+            })?;
+
+            Ok((name, path, content))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    check_max_size(&maps, args.max_size, args.allow_huge)?;
+
+    debug!(
+        "The page size is {} bytes, the allocation granularity is {} bytes",
+        page_size(),
+        allocation_granularity()
+    );
+
+    for name in &args.read_only {
+        if !maps.iter().any(|map| &map.name == name) {
+            bail!("The map {name} given to --read-only isn't one of the maps to create");
+        }
+    }
+
+    for (name, _) in &args.base_address {
+        if !maps.iter().any(|map| &map.name == name) {
+            bail!("The map {name} given to --base-address isn't one of the maps to create");
+        }
+    }
+
+    if !args.base_address.is_empty() || maps.iter().any(|map| map.base_address.is_some()) {
+        warn!(
+            "Mapping views at fixed base addresses is unsupported and only meant for \
+             compatibility experiments"
+        );
+    }
+
+    for (name, alias) in &args.alias {
+        if !maps.iter().any(|map| &map.name == name) {
+            bail!("The map {name} given to --alias isn't one of the maps to create");
+        }
+
+        if maps.iter().any(|map| map.file_name(prefix) == *alias) {
+            bail!("The alias {alias} would replace the file of one of the maps to create");
+        }
+    }
+
+    // The padding takes up space as well.
+    let allocated = maps
+        .iter()
+        .map(|map| {
+            let size = map.size.checked_add(padding).with_context(|| {
+                format!("The size of the map {} including the padding is too large", map.name)
+            })?;
+
+            Ok(MapSpec::new(map.name.clone(), size))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let shm_dir = match huge_pages {
+        Some(huge_pages) => {
+            info!("Using the hugetlbfs at {}", huge_pages.dir.path.display());
+            huge_pages.dir
+        }
+        None => shm_dir_for_maps(global, &allocated)?,
+    };
+
+    check_tmpfs(&shm_dir, args.allow_non_tmpfs)?;
+
+    if args.space_report {
+        print_space_report(&shm_dir, prefix, &allocated)?;
+    }
+
+    check_free_space(&shm_dir, prefix, &allocated)?;
+
+    if args.space_report {
+        return Ok(());
+    }
+
+    let grace_period = Duration::try_from_secs_f64(args.grace_period)
+        .context("The grace period needs to be a positive number of seconds")?;
+    let timeout = args
+        .timeout
+        .map(Duration::try_from_secs_f64)
+        .transpose()
+        .context("The timeout needs to be a positive number of seconds")?;
+    let watch_adoption =
+        args.watch_adoption.map(Duration::try_from_secs_f64).transpose().context(
+            "The --watch-adoption grace period needs to be a positive number of seconds",
+        )?;
+    let watch_maps = args
+        .watch_maps
+        .map(Duration::try_from_secs_f64)
+        .transpose()
+        .context("The --watch-maps interval needs to be a positive number of seconds")?;
+
+    if watch_maps.is_some_and(|interval| interval.is_zero()) {
+        bail!("The --watch-maps interval needs to be larger than zero");
+    }
+
+    let touch_interval = args
+        .touch_interval
+        .map(Duration::try_from_secs_f64)
+        .transpose()
+        .context("The --touch-interval needs to be a positive number of seconds")?;
+
+    if touch_interval.is_some_and(|interval| interval.is_zero()) {
+        bail!("The --touch-interval needs to be larger than zero");
+    }
+
+    let attributes = args.attributes.unwrap_or(bridge::DEFAULT_FILE_ATTRIBUTES);
+
+    if attributes != bridge::DEFAULT_FILE_ATTRIBUTES {
+        warn!(
+            "Creating the files with the attributes {attributes:#x} instead of {:#x}, this is \
+             unsupported and might make the simulator ignore the files",
+            bridge::DEFAULT_FILE_ATTRIBUTES
+        );
+    }
+
+    if args.dry_run {
+        for map in &maps {
+            let action = if args.attach.contains(&map.name) { "attach" } else { "create" };
+
+            println!(
+                "{action} {} {}",
+                shm_dir.path.join(map.file_name(prefix)).display(),
+                map.size
+            );
+        }
+
+        for (name, alias) in &args.alias {
+            let target = map_spec::file_name(name, prefix);
+
+            println!(
+                "link {} {}",
+                shm_dir.path.join(alias).display(),
+                shm_dir.path.join(target).display()
+            );
+        }
+
+        return Ok(());
+    }
+
+    // Set a console control handler which will unpark our thread and let main
+    // finish. The handler is set up before the mappings are created, so a CTRL-C
+    // during the creation rolls back the mappings created so far.
+    //
+    // The handler waits for the `shutdown` object to be dropped for the
+    // termination events, so it needs to be dropped after everything else.
+    //
+    // If we aren't going to park, there's nothing to shut down, so we don't
+    // need a handler.
+    //
+    // Failing to set up the handler isn't fatal if the bridge stops on its own,
+    // CTRL-C terminates it without removing the files, so only the timeout or
+    // the consumers going away make it clean up. Otherwise the bridge could only
+    // be killed, leaving the files behind, so we refuse to start.
+    let mut has_handler = true;
+    let shutdown = if args.no_park {
+        None
+    } else {
+        Some(Shutdown::install().or_else(|e| {
+            if timeout.is_none() && !args.wait_for_consumer {
+                return Err(e.context(
+                    "The bridge would never remove the /dev/shm backed files without a console \
+                     control handler, use --timeout to let it shut down on its own, or use \
+                     --no-park and remove the files using the clean subcommand",
+                ));
+            }
+
+            warn!(
+                "{e:#}, CTRL-C will terminate the bridge without removing the /dev/shm backed \
+                 files"
+            );
+            has_handler = false;
+
+            Ok(Shutdown::without_handler())
+        })?)
+    };
+
+    // Make sure no other instance of the bridge manages the same maps, otherwise
+    // its clean up would remove the files from under us.
+    let mut locks = maps
+        .iter()
+        .map(|map| Ok((map.name.clone(), MapLock::acquire(&shm_dir.path, &map.file_name(prefix))?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    // The files of attached maps belong to a Linux process, a later clean up
+    // shouldn't remove them.
+    let owned_maps: Vec<_> =
+        maps.iter().filter(|map| !args.attach.contains(&map.name)).cloned().collect();
+
+    let mut pid_file = match &args.pid_file {
+        Some(path) => Some(PidFile::new(&shm_dir.path, prefix, &owned_maps).write(path)?),
+        None => None,
+    };
+
+    // A reload adds and removes maps, so it takes care of their locks and of
+    // the PID file from now on.
+    if let Some(reload) = &mut reload {
+        reload.locks = std::mem::take(&mut locks);
+        reload.pid_file = pid_file.take();
+    }
+
+    let gid = args.group.as_deref().map(unix::resolve_group).transpose()?;
+
+    // Create the mappings and park until we should shut down.
+    let linux_dir = shm_dir.linux_path();
+
+    let mut run = || -> Result<()> {
+        let mut bridge = Bridge::new(&shm_dir.path);
+        bridge
+            .force(args.force)
+            .migrate(args.migrate)
+            .zero(!args.no_zero)
+            .mode(args.mode)
+            .group(gid)
+            .file_attributes(attributes)
+            .padding(padding)
+            .verify(args.verify)
+            .quiet_on_reuse(args.quiet_on_reuse)
+            .best_effort(args.best_effort)
+            .strict_sizes(args.strict_sizes)
+            .sync(args.sync)
+            .retry_policy(global.retry_policy())
+            .prefix(prefix)
+            .keep_files(args.keep_on_exit || args.no_park);
+
+        for MapSpec { name, size, .. } in &maps {
+            bridge.add_map(name, *size);
+        }
+
+        for (name, alias) in &args.alias {
+            bridge.add_alias(name, alias);
+        }
+
+        for name in &read_only {
+            bridge.add_read_only(*name);
+        }
+
+        for name in &args.attach {
+            bridge.attach(name);
+        }
+
+        for (name, address) in &args.base_address {
+            bridge.base_address(name, *address);
+        }
+
+        bridge.start()?;
+
+        // With `--best-effort` only some of the maps might have been created.
+        let failures = bridge.take_failures();
+        let maps = bridge.maps().to_vec();
+        let owned_maps: Vec<_> =
+            maps.iter().filter(|map| !args.attach.contains(&map.name)).cloned().collect();
+        let (status, target) = if failures.is_empty() {
+            ("All mappings were successfully created", logging::SUCCESS)
+        } else {
+            ("The remaining mappings were created", module_path!())
+        };
+
+        for (name, path, content) in &init_content {
+            if !maps.iter().any(|map| &map.name == *name) {
+                continue;
+            }
+
+            let copied = bridge.write_map(name, content)?;
+            info!("Initialized the map {name} with {copied} bytes from {}", path.display());
+        }
+
+        print_paths(&shm_dir, prefix, &maps);
+
+        let bytes = maps.iter().map(|map| map.size as u64).sum();
+        let noun = if maps.len() == 1 { "map" } else { "maps" };
+        let units = if si_units { ", using decimal size units" } else { "" };
+        info!(
+            "Created {} {noun} totaling {} in {}{units}",
+            maps.len(),
+            format_size_with(bytes, si_units),
+            shm_dir.path.display()
+        );
+
+        let count = |status| {
+            let names: Vec<_> = bridge
+                .statuses()
+                .iter()
+                .filter(|(_, s)| *s == status)
+                .map(|(name, _)| name.as_str())
+                .collect();
+
+            if !names.is_empty() {
+                info!("    {status}: {}", names.join(", "));
+            }
+
+            names.len()
+        };
+        let (created, reused, recreated, attached) = (
+            count(MapStatus::Created),
+            count(MapStatus::Reused),
+            count(MapStatus::Recreated),
+            count(MapStatus::Attached),
+        );
+
+        output::emit(&Event::Summary {
+            maps: maps.len(),
+            bytes,
+            dir: &shm_dir.path,
+            created,
+            reused,
+            recreated,
+            attached,
+        });
+
+        if !failures.is_empty() {
+            warn!("The mappings of the following maps couldn't be created:");
+
+            for (name, e) in &failures {
+                warn!("    {name}: {e:#}");
+            }
+        }
+
+        if let Some(command) = &args.on_ready {
+            hooks::start("--on-ready", command, linux_dir.as_deref());
+        }
+
+        let Some(shutdown) = &shutdown else {
+            info!(target: target, "{status}.");
+            print_clean_command(&shm_dir, prefix, &owned_maps, &args.alias);
+
+            return creation_result(failures);
+        };
+
+        let mut status_file = args.status_file.as_ref().map(|path| {
+            StatusFile::new(
+                path.clone().unwrap_or_else(|| status_file::default_path(&shm_dir.path, prefix)),
+            )
+        });
+
+        if let Some(status_file) = &mut status_file {
+            status_file.update(&bridge)?;
+            info!("Writing the status of the maps to {}", status_file.path().display());
+        }
+
+        if !has_handler {
+            let command = match &args.pid_file {
+                Some(path) => format!("shm-bridge.exe clean --from-pid-file {}", path.display()),
+                None => clean_command(&shm_dir, prefix, &owned_maps, &args.alias),
+            };
+
+            warn!(
+                "The bridge can't clean up if it gets terminated, remove the /dev/shm backed files \
+                 afterwards using:\n    {command}"
+            );
+        }
+
+        if let Some(grace_period) = watch_adoption {
+            let maps = maps.iter().map(|map| (map.name.clone(), bridge.file_path(map))).collect();
+
+            adoption::watch(maps, grace_period);
+        }
+
+        // Park the main thread so we don't exit and don't drop the `FileMapping`
+        // objects. If the bridge breaks while it's parked, it's still cleaned up
+        // before the error is returned.
+        let mut parked = Ok(());
+
+        if args.wait_for_consumer {
+            info!(target: target, "{status}.");
+
+            let paths: Vec<_> = maps.iter().map(|map| bridge.file_path(map)).collect();
+            consumers::wait_for_consumers(shutdown, &paths, grace_period);
+        } else if let Some(timeout) = timeout {
+            info!(
+                target: target,
+                "{status}, shutting down in {} seconds, press CTRL-C to exit earlier.",
+                timeout.as_secs_f64()
+            );
+
+            parked = park(
+                shutdown,
+                &mut bridge,
+                Some(timeout),
+                watch_maps,
+                touch_interval,
+                reload.as_mut(),
+                status_file.as_mut(),
+            )
+            .map(|elapsed| {
+                if elapsed {
+                    info!("The timeout has elapsed.");
+                }
+            });
+        } else {
+            info!(target: target, "{status}, press CTRL-C to exit.");
+
+            parked = park(
+                shutdown,
+                &mut bridge,
+                None,
+                watch_maps,
+                touch_interval,
+                reload.as_mut(),
+                status_file.as_mut(),
+            )
+            .map(|_| ());
+        }
+
+        if let Err(e) = &parked {
+            warn!("The bridge can't keep running: {e:#}");
+        }
+
+        info!("\nShutting down.");
+
+        if let Some(command) = &args.on_cleanup {
+            hooks::run("--on-cleanup", command, linux_dir.as_deref());
+        }
+
+        if args.keep_on_exit {
+            print_clean_command(&shm_dir, prefix, &owned_maps, &args.alias);
+            parked?;
+
+            return creation_result(failures);
+        }
+
+        // The console control handler has unparked us, the consumers are gone, or the
+        // timeout elapsed, we should stop running so let's unlink the `/dev/shm` files.
+        let summary = bridge.cleanup();
+
+        if summary.is_clean() {
+            info!(target: logging::SUCCESS, "Shut down cleanly, {summary}");
+        } else {
+            warn!("Shut down with an incomplete clean up, {summary}");
+        }
+
+        report_clean_up(summary)?;
+        parked?;
+
+        creation_result(failures)
+    };
+
+    let mut restarts = 0;
+    let mut delay = RESTART_DELAY;
+
+    loop {
+        match run() {
+            Err(e)
+                if args.restart_on_crash
+                    && restarts < args.max_restarts
+                    && !shutdown::is_requested() =>
+            {
+                restarts += 1;
+
+                warn!(
+                    "The bridge failed, restarting in {} seconds ({restarts} of {} restarts): \
+                     {e:#}",
+                    delay.as_secs_f64(),
+                    args.max_restarts
+                );
+
+                // A CTRL-C while we wait for the restart stops the bridge.
+                if shutdown.as_ref().is_some_and(|shutdown| shutdown.wait_timeout(delay)) {
+                    return Ok(());
+                }
+
+                delay = (delay * 2).min(MAX_RESTART_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
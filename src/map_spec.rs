@@ -0,0 +1,506 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    error::Error,
+    size::{parse_size_with, SizeArg},
+};
+
+/// A shared memory mapping the bridge should create.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapSpec {
+    /// The name of the mapping, this is the name the Windows application uses
+    /// to open the named shared memory.
+    pub name: String,
+    /// The size of the mapping in bytes.
+    pub size: usize,
+    /// The protection of the mapping.
+    pub access: Access,
+    /// The address the views of the mapping should be mapped at.
+    pub base_address: Option<usize>,
+}
+
+/// A map as it's given in a config file.
+///
+/// The size is only interpreted once it's known if its suffix uses decimal
+/// multipliers, see [`MapEntry::resolve()`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MapEntry {
+    /// The name of the mapping.
+    pub name: String,
+    /// The size of the mapping, either a plain number of bytes or a
+    /// human-readable string, e.g. `"32M"`.
+    #[serde(deserialize_with = "deserialize_size")]
+    pub size: SizeArg,
+    /// The protection of the mapping, maps are writable unless `ro` is given.
+    #[serde(default, deserialize_with = "deserialize_access")]
+    pub access: Access,
+    /// The address the views of the mapping should be mapped at, given as a
+    /// hexadecimal string, i.e. `"0x7f0000000000"`, or as a plain number.
+    #[serde(default, deserialize_with = "deserialize_base_address")]
+    pub base_address: Option<usize>,
+}
+
+impl MapEntry {
+    /// Turn the entry into a [`MapSpec`], the suffix of the size uses decimal
+    /// multipliers if `si_units` is set.
+    pub fn resolve(self, si_units: bool) -> MapSpec {
+        let MapEntry { name, size, access, base_address } = self;
+
+        MapSpec { name, size: size.resolve(si_units), access, base_address }
+    }
+}
+
+/// The protection a map is created with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Access {
+    /// Windows applications can read from and write to the map, given as `rw`.
+    #[default]
+    ReadWrite,
+    /// Windows applications can only read from the map, given as `ro`. This is
+    /// the same as passing the map to `--read-only`.
+    ReadOnly,
+}
+
+impl Access {
+    /// Parse the access of a map, either `rw` or `ro`.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input {
+            "rw" => Ok(Self::ReadWrite),
+            "ro" => Ok(Self::ReadOnly),
+            _ => Err(format!(
+                "The access `{input}` isn't supported, use `rw` for a writable map or `ro` for \
+                 a read-only one"
+            )),
+        }
+    }
+}
+
+/// Deserialize the access of a map, see [`Access::parse()`].
+fn deserialize_access<'de, D>(deserializer: D) -> Result<Access, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let access = String::deserialize(deserializer)?;
+    Access::parse(&access).map_err(serde::de::Error::custom)
+}
+
+/// Deserialize a size which is either given as a plain number of bytes or as a
+/// human-readable string, e.g. `"32M"`.
+fn deserialize_size<'de, D>(deserializer: D) -> Result<SizeArg, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(usize),
+        HumanReadable(String),
+    }
+
+    match Size::deserialize(deserializer)? {
+        Size::Bytes(size) => Ok(size.into()),
+        Size::HumanReadable(size) => SizeArg::parse(&size).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Parse a hexadecimal address, optionally prefixed with `0x`.
+///
+/// This is meant to be used as a clap `value_parser`, so the error is a
+/// human-readable string.
+pub fn parse_address(input: &str) -> Result<usize, String> {
+    let digits = input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")).unwrap_or(input);
+
+    match usize::from_str_radix(digits, 16) {
+        Ok(0) => Err(format!("The address `{input}` needs to be larger than zero")),
+        Ok(address) => Ok(address),
+        Err(e) => Err(format!("The address `{input}` isn't a valid hexadecimal number: {e}")),
+    }
+}
+
+/// Deserialize a base address which is either given as a plain number or as a
+/// hexadecimal string, see [`parse_address()`].
+fn deserialize_base_address<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Address {
+        Number(usize),
+        Hexadecimal(String),
+    }
+
+    match Address::deserialize(deserializer)? {
+        Address::Number(0) => Err(serde::de::Error::custom("The base address can't be zero")),
+        Address::Number(address) => Ok(Some(address)),
+        Address::Hexadecimal(address) => {
+            parse_address(&address).map(Some).map_err(serde::de::Error::custom)
+        }
+    }
+}
+
+/// The namespace prefixes a Win32 object name may start with.
+const NAMESPACES: &[&str] = &["Local\\", "Global\\"];
+
+/// Get the name of the `/dev/shm` backed file for the map with the given Win32
+/// name.
+///
+/// The `Local\` or `Global\` namespace prefix is stripped from the name and
+/// any remaining backslash is replaced with an underscore, so the file always
+/// ends up directly inside the shm directory. For example, the map
+/// `Local\$iRSDKMemMapFileName` is backed by the file `$iRSDKMemMapFileName`.
+///
+/// The given prefix is prepended to the file name, this lets multiple bridge
+/// instances, whose maps use the same names, share a shm directory. The Win32
+/// names of the maps aren't affected by it.
+pub fn file_name(name: &str, prefix: &str) -> String {
+    let name = NAMESPACES
+        .iter()
+        .find_map(|namespace| {
+            name.get(..namespace.len())
+                .filter(|prefix| prefix.eq_ignore_ascii_case(namespace))
+                .map(|_| &name[namespace.len()..])
+        })
+        .unwrap_or(name);
+
+    format!("{prefix}{}", name.replace('\\', "_"))
+}
+
+impl MapSpec {
+    /// Create a writable map with the given name and size.
+    pub fn new(name: impl Into<String>, size: usize) -> Self {
+        Self { name: name.into(), size, access: Access::default(), base_address: None }
+    }
+
+    /// Get the name of the `/dev/shm` backed file of this map using the given
+    /// prefix, see [`file_name()`].
+    pub fn file_name(&self, prefix: &str) -> String {
+        file_name(&self.name, prefix)
+    }
+
+    /// Check that the file name of the map can be used as a file name inside
+    /// the shm directory.
+    ///
+    /// Path separators would place the file somewhere else, while the null byte
+    /// and the characters Windows reserves for paths can't be used in file
+    /// names at all. Simulators tend to use letters, digits, and characters
+    /// like `$` or `_` which are all allowed.
+    pub fn validate_name(&self) -> Result<(), String> {
+        /// The characters Windows doesn't allow in file names, besides the
+        /// control characters.
+        const RESERVED: &[char] = &['/', '\\', '<', '>', ':', '"', '|', '?', '*'];
+
+        let name = &self.name;
+        // The prefix is checked when it's parsed.
+        let file_name = self.file_name("");
+
+        if file_name.is_empty() || file_name == "." || file_name == ".." {
+            return Err(format!("The map name `{name}` is not a valid file name"));
+        }
+
+        match file_name.chars().find(|c| c.is_control() || RESERVED.contains(c)) {
+            Some(c) => Err(format!(
+                "The map name `{}` contains the character {c:?} which can't be used in a file name",
+                name.escape_default()
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A single `--map` argument given on the command line.
+///
+/// The size is optional, since it can either be given inline, using the
+/// `name:size` syntax, or using a separate `--size` argument.
+#[derive(Debug, Clone)]
+pub struct MapArg {
+    /// The name of the mapping.
+    pub name: String,
+    /// The size of the mapping, if it was given using the `name:size` syntax.
+    pub size: Option<SizeArg>,
+}
+
+/// Pair up the map names given on the command line with their sizes.
+///
+/// Maps which were given using the `name:size` syntax keep their inline size,
+/// the remaining maps get the `sizes` assigned in order, or `size_all` if it
+/// was given. The suffixes of the sizes use decimal multipliers if `si_units`
+/// is set.
+pub fn pair_maps(
+    maps: &[MapArg],
+    sizes: &[SizeArg],
+    size_all: Option<&SizeArg>,
+    si_units: bool,
+) -> Result<Vec<MapSpec>, Error> {
+    let resolve = |size: &SizeArg| size.resolve(si_units);
+
+    if let Some(size_all) = size_all {
+        return Ok(maps
+            .iter()
+            .map(|map| {
+                MapSpec::new(map.name.clone(), resolve(map.size.as_ref().unwrap_or(size_all)))
+            })
+            .collect());
+    }
+
+    let unsized_maps = maps.iter().filter(|map| map.size.is_none()).count();
+
+    if unsized_maps != sizes.len() {
+        return Err(Error::CountMismatch { maps: unsized_maps, sizes: sizes.len() });
+    }
+
+    let mut sizes = sizes.iter();
+
+    Ok(maps
+        .iter()
+        .map(|map| {
+            let size = map
+                .size
+                .as_ref()
+                .or_else(|| sizes.next())
+                .expect("We checked that enough sizes exist");
+
+            MapSpec::new(map.name.clone(), resolve(size))
+        })
+        .collect())
+}
+
+/// Parse a `--map` argument, either in the `name` or in the `name:size` form.
+///
+/// This is meant to be used as a clap `value_parser`.
+pub fn parse_map_arg(input: &str) -> Result<MapArg, String> {
+    let (name, size) = match input.rsplit_once(':') {
+        Some((name, size)) => (name, Some(SizeArg::parse(size)?)),
+        None => (input, None),
+    };
+
+    if name.is_empty() {
+        return Err(format!("The map `{input}` is missing a name"));
+    }
+
+    Ok(MapArg { name: name.to_owned(), size })
+}
+
+/// Parse a comma separated list of maps in the `name:size` form, i.e.
+/// `foo:1M,bar:2M`.
+///
+/// Unlike with `--map`, every map needs to have a size since there's no
+/// `--size` argument to take it from. The suffixes of the sizes use decimal
+/// multipliers if `si_units` is set.
+pub fn parse_map_list(input: &str, si_units: bool) -> Result<Vec<MapSpec>, String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|map| !map.is_empty())
+        .map(|map| {
+            let MapArg { name, size } = parse_map_arg(map)?;
+            let size = size.ok_or_else(|| format!("The map `{map}` is missing a size"))?;
+
+            Ok(MapSpec::new(name, size.resolve(si_units)))
+        })
+        .collect()
+}
+
+/// Parse a list of maps with one map per line, either in the `name size` or in
+/// the `name:size` form.
+///
+/// Empty lines and lines starting with `#` are skipped. The error contains the
+/// number of the line which couldn't be parsed. The suffixes of the sizes use
+/// decimal multipliers if `si_units` is set.
+pub fn parse_map_lines(input: &str, si_units: bool) -> Result<Vec<MapSpec>, String> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            let parse = || -> Result<MapSpec, String> {
+                let mut fields = line.split_whitespace();
+
+                let (name, size) = match (fields.next(), fields.next(), fields.next()) {
+                    (Some(name), Some(size), None) => {
+                        (name.to_owned(), parse_size_with(size, si_units)?)
+                    }
+                    (Some(_), None, None) => {
+                        let MapArg { name, size } = parse_map_arg(line)?;
+                        let size =
+                            size.ok_or_else(|| format!("The map `{line}` is missing a size"))?;
+
+                        (name, size.resolve(si_units))
+                    }
+                    _ => return Err(format!("Expected `name size` or `name:size`, got `{line}`")),
+                };
+
+                Ok(MapSpec::new(name, size))
+            };
+
+            parse().map_err(|e| format!("Line {number}: {e}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(name: &str, size: Option<usize>) -> MapArg {
+        MapArg { name: name.to_owned(), size: size.map(SizeArg::from) }
+    }
+
+    fn sizes(sizes: &[usize]) -> Vec<SizeArg> {
+        sizes.iter().copied().map(SizeArg::from).collect()
+    }
+
+    fn spec(name: &str, size: usize) -> MapSpec {
+        MapSpec::new(name, size)
+    }
+
+    #[test]
+    fn pair_equal_counts() {
+        let maps = [map("first", None), map("second", None)];
+
+        let paired = pair_maps(&maps, &sizes(&[1024, 2048]), None, false).unwrap();
+
+        assert_eq!(paired, [spec("first", 1024), spec("second", 2048)]);
+    }
+
+    #[test]
+    fn pair_inline_sizes() {
+        let maps = [map("first", Some(4096)), map("second", None), map("third", Some(16))];
+
+        let paired = pair_maps(&maps, &sizes(&[2048]), None, false).unwrap();
+
+        assert_eq!(paired, [spec("first", 4096), spec("second", 2048), spec("third", 16)]);
+    }
+
+    #[test]
+    fn pair_mismatched_counts() {
+        let maps = [map("first", None), map("second", None)];
+
+        let error = pair_maps(&maps, &sizes(&[1024]), None, false).unwrap_err();
+        assert!(matches!(error, Error::CountMismatch { maps: 2, sizes: 1 }));
+
+        let error = pair_maps(&maps, &sizes(&[1024, 2048, 4096]), None, false).unwrap_err();
+        assert!(matches!(error, Error::CountMismatch { maps: 2, sizes: 3 }));
+    }
+
+    #[test]
+    fn pair_empty_maps() {
+        assert_eq!(pair_maps(&[], &sizes(&[]), None, false).unwrap(), []);
+
+        let error = pair_maps(&[], &sizes(&[1024]), None, false).unwrap_err();
+        assert!(matches!(error, Error::CountMismatch { maps: 0, sizes: 1 }));
+    }
+
+    #[test]
+    fn pair_size_all() {
+        let maps = [map("first", None), map("second", Some(16))];
+
+        let paired = pair_maps(&maps, &[], Some(&4096.into()), false).unwrap();
+
+        assert_eq!(paired, [spec("first", 4096), spec("second", 16)]);
+        assert_eq!(pair_maps(&[], &[], Some(&4096.into()), false).unwrap(), []);
+    }
+
+    #[test]
+    fn parse_list() {
+        let maps = parse_map_list("first:1K, second:2048,", false).unwrap();
+
+        assert_eq!(maps, [spec("first", 1024), spec("second", 2048)]);
+        assert_eq!(parse_map_list("", false).unwrap(), []);
+    }
+
+    #[test]
+    fn parse_lines() {
+        let maps = parse_map_lines("# generated\nfirst 1K\n\n  second:2048\n", false).unwrap();
+
+        assert_eq!(maps, [spec("first", 1024), spec("second", 2048)]);
+        assert_eq!(parse_map_lines("", false).unwrap(), []);
+    }
+
+    #[test]
+    fn parse_lines_reports_the_line_number() {
+        let error = parse_map_lines("first 1K\nsecond\n", false).unwrap_err();
+        assert_eq!(error, "Line 2: The map `second` is missing a size");
+
+        let error = parse_map_lines("first 1K 2K", false).unwrap_err();
+        assert_eq!(error, "Line 1: Expected `name size` or `name:size`, got `first 1K 2K`");
+    }
+
+    #[test]
+    fn parse_list_without_size() {
+        let error = parse_map_list("first:1K,second", false).unwrap_err();
+
+        assert_eq!(error, "The map `second` is missing a size");
+    }
+
+    #[test]
+    fn si_sizes() {
+        let maps = [map("first", None), map("second", None)];
+        let sizes = [SizeArg::parse("1K").unwrap(), SizeArg::parse("2M").unwrap()];
+
+        let paired = pair_maps(&maps, &sizes, None, true).unwrap();
+        assert_eq!(paired, [spec("first", 1000), spec("second", 2_000_000)]);
+
+        assert_eq!(parse_map_list("first:1K", true).unwrap(), [spec("first", 1000)]);
+        assert_eq!(parse_map_lines("first 1K", true).unwrap(), [spec("first", 1000)]);
+
+        let map: MapEntry = serde_json::from_str(r#"{ "name": "feed", "size": "1K" }"#).unwrap();
+        assert_eq!(map.clone().resolve(false), spec("feed", 1024));
+        assert_eq!(map.resolve(true), spec("feed", 1000));
+    }
+
+    #[test]
+    fn deserialize_access() {
+        let map: MapEntry = serde_json::from_str(r#"{ "name": "feed", "size": 16 }"#).unwrap();
+        assert_eq!(map.access, Access::ReadWrite);
+
+        let map: MapEntry =
+            serde_json::from_str(r#"{ "name": "feed", "size": 16, "access": "ro" }"#).unwrap();
+        assert_eq!(map.access, Access::ReadOnly);
+
+        let error =
+            serde_json::from_str::<MapEntry>(r#"{ "name": "feed", "size": 16, "access": "wo" }"#)
+                .unwrap_err();
+        assert!(error.to_string().contains("The access `wo` isn't supported"));
+    }
+
+    #[test]
+    fn deserialize_base_address() {
+        let map: MapEntry =
+            serde_json::from_str(r#"{ "name": "feed", "size": 16, "base_address": "0x10000" }"#)
+                .unwrap();
+        assert_eq!(map.base_address, Some(0x10000));
+
+        let map: MapEntry =
+            serde_json::from_str(r#"{ "name": "feed", "size": 16, "base_address": 65536 }"#)
+                .unwrap();
+        assert_eq!(map.base_address, Some(0x10000));
+
+        assert_eq!(spec("feed", 16).base_address, None);
+        assert!(parse_address("0x").is_err());
+        assert!(parse_address("0").is_err());
+        assert!(parse_address("fixed").is_err());
+    }
+}
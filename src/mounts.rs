@@ -0,0 +1,204 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use nom::{
+    bytes::complete::is_not,
+    character::complete::space1,
+    sequence::{preceded, tuple},
+    IResult,
+};
+
+/// The path of the mount table the kernel exposes.
+///
+/// Wine maps the Linux root directory to the `Z:` drive, so this is readable
+/// from inside the Wine environment as `Z:\proc\mounts`.
+const PROC_MOUNTS_PATH: &str = "/proc/mounts";
+
+/// The path of the static filesystem table, used as a fallback if
+/// [`PROC_MOUNTS_PATH`] isn't available.
+const FSTAB_PATH: &str = "/etc/fstab";
+
+/// A single entry of a mount table, i.e. `/proc/mounts`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    /// The directory the filesystem is mounted at.
+    pub mount_point: PathBuf,
+    /// The type of the filesystem, e.g. `tmpfs`.
+    pub fs_type: String,
+}
+
+impl Mount {
+    /// Is the filesystem of this mount backed by memory instead of a disk.
+    pub fn is_memory_backed(&self) -> bool {
+        matches!(self.fs_type.as_str(), "tmpfs" | "ramfs")
+    }
+}
+
+/// Read and parse the `/proc/mounts` mount table.
+pub fn read_proc_mounts() -> Result<Vec<Mount>> {
+    read_mount_table(Path::new(PROC_MOUNTS_PATH))
+}
+
+/// Read and parse the `/etc/fstab` filesystem table.
+///
+/// Unlike `/proc/mounts`, the filesystems listed here aren't necessarily
+/// mounted.
+pub fn read_fstab() -> Result<Vec<Mount>> {
+    read_mount_table(Path::new(FSTAB_PATH))
+}
+
+fn read_mount_table(path: &Path) -> Result<Vec<Mount>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read the mount table at {}", path.display()))?;
+
+    Ok(parse_mount_table(&content))
+}
+
+/// Parse a mount table in the `fstab(5)` format.
+///
+/// Empty lines, comments, and lines that don't contain at least the device,
+/// mount point, and filesystem type fields are skipped.
+pub fn parse_mount_table(content: &str) -> Vec<Mount> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| mount_entry(line).ok().map(|(_, mount)| mount))
+        .collect()
+}
+
+/// Find the mount the given path resides on.
+///
+/// This is the mount with the longest mount point the path starts with, if a
+/// directory has been mounted over multiple times, the last mount wins.
+pub fn mount_for_path<'a>(mounts: &'a [Mount], path: &Path) -> Option<&'a Mount> {
+    mounts
+        .iter()
+        .filter(|mount| path.starts_with(&mount.mount_point))
+        .max_by_key(|mount| mount.mount_point.components().count())
+}
+
+fn field(input: &str) -> IResult<&str, &str> {
+    is_not(" \t")(input)
+}
+
+fn mount_entry(input: &str) -> IResult<&str, Mount> {
+    let (input, (_device, mount_point, fs_type)) =
+        tuple((field, preceded(space1, field), preceded(space1, field)))(input)?;
+
+    let mount =
+        Mount { mount_point: PathBuf::from(unescape(mount_point)), fs_type: fs_type.into() };
+
+    Ok((input, mount))
+}
+
+/// Replace the octal escape sequences the mount table uses for whitespace and
+/// backslashes, e.g. `\040` for a space, with the characters they represent.
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut rest = field;
+
+    while let Some(position) = rest.find('\\') {
+        unescaped.push_str(&rest[..position]);
+        rest = &rest[position..];
+
+        let character =
+            rest.get(1..4).and_then(|octal| u8::from_str_radix(octal, 8).ok()).map(char::from);
+
+        match character {
+            Some(character) => {
+                unescaped.push(character);
+                rest = &rest[4..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(mount_point: &str, fs_type: &str) -> Mount {
+        Mount { mount_point: PathBuf::from(mount_point), fs_type: fs_type.into() }
+    }
+
+    #[test]
+    fn mount_table() {
+        let content = "\
+            # /etc/fstab: static file system information\n\
+            \n\
+            proc /proc proc rw,nosuid,nodev,noexec 0 0\n\
+            \ttmpfs\t/dev/shm\ttmpfs\trw,nosuid,nodev 0 0\n  \n\
+            /dev/sda1 /mnt/with\\040space ext4 rw 0 0\n";
+
+        assert_eq!(
+            parse_mount_table(content),
+            [mount("/proc", "proc"), mount("/dev/shm", "tmpfs"), mount("/mnt/with space", "ext4"),]
+        );
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        let content = "tmpfs /dev/shm\nnone\ntmpfs /run tmpfs rw 0 0\n";
+
+        assert_eq!(parse_mount_table(content), [mount("/run", "tmpfs")]);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(unescape("/mnt/with\\040space"), "/mnt/with space");
+        assert_eq!(unescape("\\011tab\\012"), "\ttab\n");
+        assert_eq!(unescape("back\\134slash"), "back\\slash");
+        assert_eq!(unescape("no escapes"), "no escapes");
+        // Incomplete or invalid escape sequences are kept as is.
+        assert_eq!(unescape("end\\04"), "end\\04");
+        assert_eq!(unescape("invalid\\089"), "invalid\\089");
+        assert_eq!(unescape("trailing\\"), "trailing\\");
+    }
+
+    #[test]
+    fn longest_mount_point_wins() {
+        let mounts = [mount("/", "ext4"), mount("/dev", "devtmpfs"), mount("/dev/shm", "tmpfs")];
+
+        assert_eq!(mount_for_path(&mounts, Path::new("/dev/shm/acpmf_static")), Some(&mounts[2]));
+        assert_eq!(mount_for_path(&mounts, Path::new("/dev/null")), Some(&mounts[1]));
+        assert_eq!(mount_for_path(&mounts, Path::new("/home")), Some(&mounts[0]));
+        // Only whole components count, so `/dev/shmem` isn't below `/dev/shm`.
+        assert_eq!(mount_for_path(&mounts, Path::new("/dev/shmem")), Some(&mounts[1]));
+        assert_eq!(mount_for_path(&mounts[1..], Path::new("/home")), None);
+    }
+
+    #[test]
+    fn the_last_overmount_wins() {
+        let mounts = [mount("/dev/shm", "tmpfs"), mount("/dev/shm", "ramfs")];
+
+        assert_eq!(mount_for_path(&mounts, Path::new("/dev/shm/acpmf_static")), Some(&mounts[1]));
+    }
+}
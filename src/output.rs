@@ -0,0 +1,129 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::{
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::bridge::MapStatus;
+
+/// Is the JSON output format enabled.
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// The format of the output the bridge prints to stdout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable status messages.
+    #[default]
+    Human,
+    /// A JSON object per line for each event.
+    Json,
+}
+
+/// An event the bridge reports in the JSON output format.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// A mapping has been created.
+    MappingCreated {
+        /// The name of the mapping.
+        name: &'a str,
+        /// The size of the mapping in bytes.
+        size: usize,
+        /// The path of the `/dev/shm` backed file of the mapping.
+        path: &'a Path,
+        /// Was the file created, reused, or recreated.
+        status: MapStatus,
+    },
+    /// The `/dev/shm` backed file of a mapping has been removed.
+    MappingRemoved {
+        /// The name of the mapping.
+        name: &'a str,
+        /// The path of the removed file.
+        path: &'a Path,
+    },
+    /// All the mappings have been created.
+    Summary {
+        /// The number of created mappings.
+        maps: usize,
+        /// The total size of the mappings in bytes.
+        bytes: u64,
+        /// The directory the `/dev/shm` backed files were placed in.
+        dir: &'a Path,
+        /// The number of mappings whose files were newly created.
+        created: usize,
+        /// The number of mappings whose existing files were reused.
+        reused: usize,
+        /// The number of mappings whose existing files were replaced.
+        recreated: usize,
+        /// The number of mappings which were attached to files a Linux process
+        /// created.
+        attached: usize,
+    },
+    /// The `/dev/shm` backed file of a mapping couldn't be removed.
+    RemovalFailed {
+        /// The name of the mapping.
+        name: &'a str,
+        /// The path of the file.
+        path: &'a Path,
+        /// The error message.
+        message: String,
+    },
+    /// The `/dev/shm` backed files of the maps have been cleaned up.
+    CleanUpSummary {
+        /// The number of removed files.
+        removed: usize,
+        /// The number of files which were already absent.
+        absent: usize,
+        /// The number of files which couldn't be removed.
+        failed: usize,
+    },
+    /// An error occurred and the bridge is going to exit.
+    Error {
+        /// The error message.
+        message: String,
+    },
+}
+
+/// Select the format of the output.
+pub fn init(format: OutputFormat) {
+    JSON_OUTPUT.store(format == OutputFormat::Json, Ordering::Relaxed);
+}
+
+/// Is the JSON output format enabled.
+pub fn is_json() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Report the given event.
+///
+/// The event is printed to stdout as a single line of JSON if the JSON output
+/// format is enabled, otherwise this does nothing, the human-readable status
+/// messages are printed using the logger instead.
+pub fn emit(event: &Event<'_>) {
+    if is_json() {
+        let json = serde_json::to_string(event).expect("Events can always be serialized");
+        println!("{json}");
+    }
+}
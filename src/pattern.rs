@@ -0,0 +1,102 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+/// Check if the given name matches a shell-style wildcard pattern.
+///
+/// A `*` in the pattern matches any number of characters, including none,
+/// while a `?` matches exactly one character. Every other character only
+/// matches itself.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // The position after the last `*` we have seen, and the position in the name
+    // the `*` is currently matched up to, so we can backtrack.
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Check if the given pattern matches every name, i.e. it only consists of `*`
+/// characters.
+pub fn matches_everything(pattern: &str) -> bool {
+    pattern.chars().all(|c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_patterns() {
+        assert!(matches("acpmf_static", "acpmf_static"));
+        assert!(!matches("acpmf_static", "acpmf_physics"));
+        assert!(!matches("acpmf", "acpmf_static"));
+        assert!(matches("", ""));
+    }
+
+    #[test]
+    fn star() {
+        assert!(matches("acpmf_*", "acpmf_static"));
+        assert!(matches("acpmf_*", "acpmf_"));
+        assert!(matches("*", "anything"));
+        assert!(matches("*_physics", "acpmf_physics"));
+        assert!(matches("a*_*s", "acpmf_physics"));
+        assert!(!matches("acpmf_*", "IRSDKMemMapFileName"));
+        assert!(!matches("*_static", "acpmf_physics"));
+    }
+
+    #[test]
+    fn question_mark() {
+        assert!(matches("acpmf_?tatic", "acpmf_static"));
+        assert!(!matches("acpmf_?", "acpmf_"));
+        assert!(!matches("acpmf_?", "acpmf_ab"));
+    }
+
+    #[test]
+    fn bare_stars_match_everything() {
+        assert!(matches_everything("*"));
+        assert!(matches_everything("**"));
+        assert!(!matches_everything("ac_*"));
+        assert!(!matches_everything("?*"));
+    }
+}
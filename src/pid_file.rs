@@ -0,0 +1,175 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::{
+    fmt::Write,
+    fs::remove_file,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use windows::Win32::{
+    Foundation::{CloseHandle, STILL_ACTIVE},
+    System::Threading::{GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION},
+};
+
+use crate::map_spec::MapSpec;
+
+/// The content of a PID file, it records which maps a bridge instance manages.
+///
+/// The PID file is a line based text file, the first line contains the process
+/// ID, the second line contains the directory the maps are placed in, and the
+/// remaining lines contain a map each, with the size in front of the name. If
+/// a file prefix is used, it's recorded in a `prefix` line after the
+/// directory:
+///
+/// ```text
+/// pid 1234
+/// dir /dev/shm/
+/// prefix sim1_
+/// map 2048 acpmf_static
+/// ```
+#[derive(Debug, Clone)]
+pub struct PidFile {
+    /// The process ID of the bridge, this is the Wine process ID, not the one
+    /// the Linux side sees.
+    pub pid: u32,
+    /// The directory the `/dev/shm` backed files of the maps are placed in.
+    pub dir: PathBuf,
+    /// The prefix of the names of the `/dev/shm` backed files, see
+    /// [`file_name()`](crate::map_spec::file_name).
+    pub prefix: String,
+    /// The maps the bridge manages.
+    pub maps: Vec<MapSpec>,
+}
+
+/// Removes the PID file once it's dropped.
+pub struct PidFileGuard {
+    path: PathBuf,
+    pid_file: PidFile,
+}
+
+impl PidFileGuard {
+    /// Replace the maps recorded in the PID file, i.e. once a reload of the
+    /// config file added or removed maps.
+    pub fn update(&mut self, maps: &[MapSpec]) -> Result<()> {
+        self.pid_file.maps = maps.to_vec();
+        self.pid_file.write_content(&self.path)
+    }
+}
+
+impl Drop for PidFileGuard {
+    fn drop(&mut self) {
+        // There's not much we can do if an error happens here, so let's ignore it.
+        let _ = remove_file(&self.path);
+    }
+}
+
+impl PidFile {
+    /// Create a [`PidFile`] for the current process, whose maps use the given
+    /// file prefix.
+    pub fn new(dir: &Path, prefix: &str, maps: &[MapSpec]) -> Self {
+        Self {
+            pid: std::process::id(),
+            dir: dir.to_owned(),
+            prefix: prefix.to_owned(),
+            maps: maps.to_vec(),
+        }
+    }
+
+    /// Write the PID file to the given path.
+    ///
+    /// The file is removed again once the returned guard is dropped.
+    pub fn write(self, path: &Path) -> Result<PidFileGuard> {
+        self.write_content(path)?;
+
+        Ok(PidFileGuard { path: path.to_owned(), pid_file: self })
+    }
+
+    fn write_content(&self, path: &Path) -> Result<()> {
+        let mut content = format!("pid {}\ndir {}\n", self.pid, self.dir.display());
+
+        if !self.prefix.is_empty() {
+            writeln!(content, "prefix {}", self.prefix).expect("Writing to a String never fails");
+        }
+
+        for MapSpec { name, size, .. } in &self.maps {
+            writeln!(content, "map {size} {name}").expect("Writing to a String never fails");
+        }
+
+        std::fs::write(path, content)
+            .with_context(|| format!("Could not write the PID file {}", path.display()))
+    }
+
+    /// Read the PID file found at the given path.
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read the PID file {}", path.display()))?;
+
+        Self::parse(&content)
+            .with_context(|| format!("Could not parse the PID file {}", path.display()))
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let mut pid = None;
+        let mut dir = None;
+        let mut prefix = String::new();
+        let mut maps = Vec::new();
+
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            match line.split_once(' ') {
+                Some(("pid", value)) => pid = Some(value.trim().parse().context("Invalid PID")?),
+                Some(("dir", value)) => dir = Some(PathBuf::from(value)),
+                Some(("prefix", value)) => prefix = value.to_owned(),
+                Some(("map", value)) => {
+                    let Some((size, name)) = value.split_once(' ') else {
+                        bail!("Invalid map entry `{line}`");
+                    };
+                    let size =
+                        size.parse().with_context(|| format!("Invalid map entry `{line}`"))?;
+
+                    maps.push(MapSpec::new(name, size));
+                }
+                _ => bail!("Unknown entry `{line}`"),
+            }
+        }
+
+        let (Some(pid), Some(dir)) = (pid, dir) else {
+            bail!("The PID or directory entry is missing");
+        };
+
+        Ok(Self { pid, dir, prefix, maps })
+    }
+
+    /// Check if the process that wrote the PID file is still running.
+    pub fn is_process_alive(&self) -> bool {
+        let Ok(process) =
+            (unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, self.pid) })
+        else {
+            return false;
+        };
+
+        let mut exit_code = 0;
+        let result = unsafe { GetExitCodeProcess(process, &mut exit_code) };
+        let _ = unsafe { CloseHandle(process) };
+
+        result.is_ok() && exit_code == STILL_ACTIVE.0 as u32
+    }
+}
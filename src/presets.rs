@@ -0,0 +1,86 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fmt;
+
+use clap::ValueEnum;
+
+use crate::map_spec::MapSpec;
+
+/// A known set of shared memory mappings a simulator uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// The mappings Assetto Corsa and Assetto Corsa Competizione use.
+    #[value(alias = "ac")]
+    Acc,
+    /// The telemetry mapping iRacing uses.
+    Iracing,
+    /// The mappings the rFactor 2 shared memory plugin,
+    /// `rFactor2SharedMemoryMapPlugin64.dll`, uses in the game.
+    #[value(alias = "rf2")]
+    Rfactor2,
+}
+
+impl Preset {
+    /// Get the list of maps this preset expands to.
+    pub fn maps(self) -> Vec<MapSpec> {
+        let maps: &[(&str, usize)] = match self {
+            // TODO: Should we use the real structs from simetry for this? Seems a bit
+            // overkill.
+            Preset::Acc => &[
+                ("acpmf_crewchief", 15660),
+                ("acpmf_static", 2048),
+                ("acpmf_physics", 2048),
+                ("acpmf_graphics", 2048),
+            ],
+            // The iRacing SDK only uses a single mapping, the data valid signal and the
+            // broadcast messages use a Win32 event and window messages respectively.
+            //
+            // Names without a namespace prefix end up in the session namespace, which is the
+            // same namespace the `Local\` prefix the SDK uses refers to.
+            Preset::Iracing => &[("IRSDKMemMapFileName", 1024 * 1024)],
+            // The buffers have different sizes which depend on the version of the plugin,
+            // the largest one, the telemetry of up to 128 vehicles, stays well below 1 MiB.
+            //
+            // A dedicated server appends its PID to the names, which isn't known before
+            // the server is started, so only the maps of the game are covered.
+            Preset::Rfactor2 => &[
+                ("$rFactor2SMMP_Telemetry$", 1024 * 1024),
+                ("$rFactor2SMMP_Scoring$", 1024 * 1024),
+                ("$rFactor2SMMP_Rules$", 1024 * 1024),
+                ("$rFactor2SMMP_MultiRules$", 1024 * 1024),
+                ("$rFactor2SMMP_ForceFeedback$", 1024 * 1024),
+                ("$rFactor2SMMP_Graphics$", 1024 * 1024),
+                ("$rFactor2SMMP_PitInfo$", 1024 * 1024),
+                ("$rFactor2SMMP_Weather$", 1024 * 1024),
+                ("$rFactor2SMMP_Extended$", 1024 * 1024),
+            ],
+        };
+
+        maps.iter().map(|&(name, size)| MapSpec::new(name, size)).collect()
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.to_possible_value().expect("Presets are never skipped");
+        f.write_str(name.get_name())
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A progress line on stderr which is updated in place while the mappings are
+//! created.
+//!
+//! The line is erased before other status messages are printed, see
+//! [`clear()`], so it always stays below them.
+
+use std::{
+    io::{stderr, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+/// Is the progress line enabled.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The length of the progress line which is currently shown, zero if there is
+/// none.
+static SHOWN: Mutex<usize> = Mutex::new(0);
+
+/// Show the progress line, this should only be enabled if stderr is a
+/// terminal.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+/// Replace the progress line with the given message.
+pub fn update(message: &str) {
+    if !ENABLED.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let mut shown = SHOWN.lock().unwrap_or_else(|e| e.into_inner());
+    let mut stderr = stderr().lock();
+
+    // Overwrite the previous line with spaces, not every terminal Wine might be
+    // connected to understands escape sequences.
+    let padding = shown.saturating_sub(message.len());
+    let _ = write!(stderr, "\r{message}{:padding$}\r{message}", "");
+    let _ = stderr.flush();
+
+    *shown = message.len();
+}
+
+/// Erase the progress line, if one is shown.
+pub fn clear() {
+    let mut shown = SHOWN.lock().unwrap_or_else(|e| e.into_inner());
+
+    if *shown > 0 {
+        let mut stderr = stderr().lock();
+        let _ = write!(stderr, "\r{:width$}\r", "", width = *shown);
+        let _ = stderr.flush();
+
+        *shown = 0;
+    }
+}
+
+/// Replace the progress line with the given final message, which stays
+/// visible.
+pub fn finish(message: &str) {
+    if ENABLED.load(Ordering::SeqCst) {
+        clear();
+        eprintln!("{message}");
+    }
+}
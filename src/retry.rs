@@ -0,0 +1,235 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Retry operations which fail because of a transient condition, like a
+//! mapping the simulator created at the same time as we did or a busy
+//! filesystem.
+
+use std::{thread, time::Duration};
+
+/// Something that can wait, this lets the tests check the delays without
+/// actually sleeping.
+pub trait Clock {
+    /// Wait for the given duration.
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// A [`Clock`] which puts the current thread to sleep.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// The ways a single attempt of an operation can fail.
+#[derive(Debug)]
+pub enum Failure<E> {
+    /// The operation might succeed if it's tried again.
+    Transient(E),
+    /// Trying again won't help, the error is returned immediately.
+    Permanent(E),
+}
+
+/// Information about the current attempt, passed to the retried operation so
+/// it can log it or act differently on the last attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attempt {
+    /// The number of the attempt, starting at 1.
+    pub number: u32,
+    /// The total number of attempts that will be made.
+    pub attempts: u32,
+    /// How long we wait before the next attempt if this one fails.
+    pub delay: Duration,
+}
+
+impl Attempt {
+    /// Is this the last attempt, i.e. a transient failure will be returned
+    /// instead of being retried.
+    pub fn is_last(&self) -> bool {
+        self.number >= self.attempts
+    }
+}
+
+/// How often an operation is retried and how long we wait in between.
+///
+/// The delay is doubled after every retry, so on a busy system the later
+/// attempts have a better chance to succeed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of retries after the first attempt failed.
+    pub retries: u32,
+    /// How long we wait before the first retry.
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// The default number of retries.
+    pub const DEFAULT_RETRIES: u32 = 3;
+
+    /// The default delay before the first retry in milliseconds.
+    pub const DEFAULT_DELAY_MS: u64 = 100;
+
+    /// Create a new policy with the given number of retries and delay before
+    /// the first retry.
+    pub fn new(retries: u32, delay: Duration) -> Self {
+        Self { retries, delay }
+    }
+
+    /// The total number of attempts, including the first one.
+    pub fn attempts(&self) -> u32 {
+        self.retries.saturating_add(1)
+    }
+
+    /// How long we wait after the attempt with the given number failed.
+    pub fn delay_after(&self, attempt: u32) -> Duration {
+        let factor = 2u32.checked_pow(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+
+        self.delay.saturating_mul(factor)
+    }
+
+    /// Run the given operation until it succeeds, fails permanently, or we run
+    /// out of attempts, sleeping in between the attempts.
+    pub fn run<T, E>(
+        &self,
+        operation: impl FnMut(Attempt) -> Result<T, Failure<E>>,
+    ) -> Result<T, E> {
+        self.run_with(&mut SystemClock, operation)
+    }
+
+    /// Run the given operation like [`RetryPolicy::run()`] does, using the
+    /// given clock to wait in between the attempts.
+    pub fn run_with<T, E>(
+        &self,
+        clock: &mut impl Clock,
+        mut operation: impl FnMut(Attempt) -> Result<T, Failure<E>>,
+    ) -> Result<T, E> {
+        let attempts = self.attempts();
+        let mut number = 1;
+
+        loop {
+            let attempt = Attempt { number, attempts, delay: self.delay_after(number) };
+
+            match operation(attempt) {
+                Ok(value) => return Ok(value),
+                Err(Failure::Permanent(e)) => return Err(e),
+                Err(Failure::Transient(e)) if attempt.is_last() => return Err(e),
+                Err(Failure::Transient(_)) => {
+                    clock.sleep(attempt.delay);
+                    number += 1;
+                }
+            }
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_RETRIES, Duration::from_millis(Self::DEFAULT_DELAY_MS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A clock which records how long it was asked to sleep.
+    #[derive(Default)]
+    struct FakeClock {
+        sleeps: Vec<Duration>,
+    }
+
+    impl Clock for FakeClock {
+        fn sleep(&mut self, duration: Duration) {
+            self.sleeps.push(duration);
+        }
+    }
+
+    fn millis(millis: &[u64]) -> Vec<Duration> {
+        millis.iter().copied().map(Duration::from_millis).collect()
+    }
+
+    #[test]
+    fn transient_failures_are_retried_with_backoff() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(100));
+        let mut clock = FakeClock::default();
+        let mut seen = Vec::new();
+
+        let result: Result<(), &str> = policy.run_with(&mut clock, |attempt| {
+            seen.push(attempt.number);
+            Err(Failure::Transient("busy"))
+        });
+
+        assert_eq!(result, Err("busy"));
+        assert_eq!(seen, [1, 2, 3, 4]);
+        assert_eq!(clock.sleeps, millis(&[100, 200, 400]));
+    }
+
+    #[test]
+    fn success_stops_retrying() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(10));
+        let mut clock = FakeClock::default();
+
+        let result: Result<u32, ()> = policy.run_with(&mut clock, |attempt| {
+            if attempt.number == 3 {
+                Ok(attempt.number)
+            } else {
+                Err(Failure::Transient(()))
+            }
+        });
+
+        assert_eq!(result, Ok(3));
+        assert_eq!(clock.sleeps, millis(&[10, 20]));
+    }
+
+    #[test]
+    fn permanent_failures_are_not_retried() {
+        let policy = RetryPolicy::default();
+        let mut clock = FakeClock::default();
+
+        let result: Result<(), &str> =
+            policy.run_with(&mut clock, |_| Err(Failure::Permanent("missing")));
+
+        assert_eq!(result, Err("missing"));
+        assert!(clock.sleeps.is_empty());
+    }
+
+    #[test]
+    fn zero_retries_make_a_single_attempt() {
+        let policy = RetryPolicy::new(0, Duration::from_millis(100));
+        let mut clock = FakeClock::default();
+
+        let result: Result<bool, ()> = policy.run_with(&mut clock, |attempt| Ok(attempt.is_last()));
+
+        assert_eq!(result, Ok(true));
+        assert!(clock.sleeps.is_empty());
+    }
+
+    #[test]
+    fn delays_saturate() {
+        let policy = RetryPolicy::new(u32::MAX, Duration::from_secs(1));
+
+        assert_eq!(policy.attempts(), u32::MAX);
+        assert_eq!(policy.delay_after(1), Duration::from_secs(1));
+        assert_eq!(policy.delay_after(64), Duration::from_secs(u64::from(u32::MAX)));
+    }
+}
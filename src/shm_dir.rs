@@ -0,0 +1,614 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info, warn};
+use windows::{core::HSTRING, Win32::Storage::FileSystem::GetDiskFreeSpaceExW};
+
+use crate::{
+    error::Error,
+    file_mapping::{existing_size, round_to_page_size},
+    map_spec::MapSpec,
+    mounts::{mount_for_path, read_fstab, read_proc_mounts, Mount},
+};
+
+/// The default path for our tmpfs.
+const TMPFS_PATH: &str = "/dev/shm/";
+
+/// The path of the memory statistics the kernel exposes, they contain the
+/// default huge page size.
+const MEMINFO_PATH: &str = "/proc/meminfo";
+
+/// The value of `--shm-dir` which selects the tmpfs with the most free space.
+pub const AUTO: &str = "auto";
+
+/// The directories `--shm-dir auto` considers, in addition to the mount points
+/// of all the tmpfs filesystems.
+const AUTO_CANDIDATES: [&str; 2] = ["/dev/shm", "/run/shm"];
+
+/// A directory `--shm-dir auto` considers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Candidate {
+    /// The path of the directory.
+    path: PathBuf,
+    /// The type of the filesystem the directory is on.
+    fs_type: String,
+    /// The free space of the filesystem in bytes.
+    available: u64,
+}
+
+/// The directory the `/dev/shm` backed files of the mappings are created in.
+#[derive(Debug, Clone)]
+pub struct ShmDir {
+    /// The path of the directory.
+    pub path: PathBuf,
+    /// The type of the filesystem the directory is on, `None` if we couldn't
+    /// figure out the filesystem type.
+    pub fs_type: Option<String>,
+}
+
+impl ShmDir {
+    /// Use the given, user provided, directory to place the `/dev/shm` backed
+    /// files in.
+    ///
+    /// The directory needs to exist and be writable, the filesystem type is
+    /// looked up in `/proc/mounts`.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        if !path.is_dir() {
+            return Err(Error::TmpfsNotFound { path: path.to_owned() }.into());
+        }
+
+        // There's no reliable way to check the Linux permissions of a directory from
+        // inside Wine, so let's just try to create a file.
+        let probe = path.join(".shm-bridge-write-test");
+
+        std::fs::File::options()
+            .write(true)
+            .create_new(true)
+            .open(&probe)
+            .with_context(|| format!("The shm directory {} isn't writable", path.display()))?;
+        std::fs::remove_file(&probe)
+            .with_context(|| format!("Could not remove the write test file {}", probe.display()))?;
+
+        let fs_type = read_proc_mounts()
+            .ok()
+            .and_then(|mounts| mount_for_path(&mounts, path).map(|mount| mount.fs_type.clone()));
+
+        Ok(Self { path: path.to_owned(), fs_type })
+    }
+
+    /// Get the path of the directory as it's seen through the `Z:` drive, which
+    /// Wine maps to the Linux root directory.
+    ///
+    /// Paths which already contain a drive letter are returned as is.
+    pub fn wine_path(&self) -> PathBuf {
+        if self.path.components().next() == Some(Component::RootDir) {
+            PathBuf::from(format!("Z:{}", self.path.display()))
+        } else {
+            self.path.clone()
+        }
+    }
+
+    /// Get the path of the directory as Linux applications see it.
+    ///
+    /// Paths on the `Z:` drive are translated to the Linux root directory,
+    /// `None` is returned for paths on other drives since we don't know where
+    /// they are mapped to.
+    pub fn linux_path(&self) -> Option<String> {
+        let path = self.path.to_string_lossy().replace('\\', "/");
+
+        if path.starts_with('/') {
+            Some(path)
+        } else if path.get(..3).is_some_and(|drive| drive.eq_ignore_ascii_case("z:/")) {
+            Some(path[2..].to_owned())
+        } else {
+            None
+        }
+    }
+
+    /// Is the directory on a memory backed filesystem, i.e. a tmpfs, ramfs, or
+    /// hugetlbfs.
+    pub fn is_memory_backed(&self) -> bool {
+        matches!(self.fs_type.as_deref(), Some("tmpfs" | "ramfs" | "hugetlbfs"))
+    }
+}
+
+/// A mounted hugetlbfs, the files placed in it are backed by huge pages.
+#[derive(Debug, Clone)]
+pub struct HugePages {
+    /// The mount point of the hugetlbfs.
+    pub dir: ShmDir,
+    /// The size of a huge page in bytes, the files have to be a multiple of it.
+    pub page_size: usize,
+}
+
+/// Find a mounted hugetlbfs to place the `/dev/shm` backed files in.
+///
+/// The first hugetlbfs in `/proc/mounts` is used, it's assumed to use the
+/// default huge page size from `/proc/meminfo`, i.e. it wasn't mounted with a
+/// `pagesize` option. Returns `None` if no hugetlbfs is mounted or the huge
+/// page size couldn't be found.
+pub fn find_hugetlbfs() -> Option<HugePages> {
+    let mounts = read_proc_mounts().ok()?;
+    let mount = mounts.iter().find(|mount| mount.fs_type == "hugetlbfs")?;
+
+    let meminfo = std::fs::read_to_string(MEMINFO_PATH).ok()?;
+    let page_size = parse_huge_page_size(&meminfo)?;
+
+    debug!("Found a hugetlbfs at {} using {page_size} byte pages", mount.mount_point.display());
+
+    Some(HugePages {
+        dir: ShmDir { path: mount.mount_point.clone(), fs_type: Some(mount.fs_type.clone()) },
+        page_size,
+    })
+}
+
+/// Parse the default huge page size out of the content of `/proc/meminfo`,
+/// i.e. the `Hugepagesize:    2048 kB` line.
+fn parse_huge_page_size(meminfo: &str) -> Option<usize> {
+    let line = meminfo.lines().find_map(|line| line.strip_prefix("Hugepagesize:"))?;
+    let kilobytes: usize = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+
+    kilobytes.checked_mul(1024).filter(|size| *size > 0)
+}
+
+/// Find a suitable memory backed directory to place the `/dev/shm` backed
+/// files in.
+///
+/// The mount table in `/proc/mounts` is used to find a tmpfs, `/dev/shm` is
+/// preferred if it's a tmpfs, otherwise the first tmpfs with at least
+/// `required_space` bytes of free space is used. If `/proc/mounts` doesn't
+/// contain any tmpfs, the tmpfs entries of `/etc/fstab` are considered
+/// instead. If no tmpfs could be found, `/dev/shm` is used regardless.
+pub fn find_shm_dir(required_space: u64) -> ShmDir {
+    let mounts = shm_mounts();
+
+    for mount in mounts.iter().filter(|mount| mount.is_memory_backed()) {
+        debug!("Found a {} filesystem at {}", mount.fs_type, mount.mount_point.display());
+    }
+
+    let (shm_dir, reason) = select_shm_dir(&mounts, required_space);
+    debug!("Selected {}, {reason}", shm_dir.path.display());
+
+    shm_dir
+}
+
+/// Should the given `--shm-dir` argument select the directory automatically.
+pub fn is_auto(path: &Path) -> bool {
+    path.as_os_str() == AUTO
+}
+
+/// Find the tmpfs with the most free space, this is what `--shm-dir auto`
+/// selects.
+///
+/// The candidates are `/dev/shm`, `/run/shm`, and the mount points of all the
+/// tmpfs filesystems in the mount table. If none of them has `required_space`
+/// bytes of free space, the directory [`find_shm_dir()`] picks is used.
+pub fn find_shm_dir_auto(required_space: u64) -> ShmDir {
+    let mounts = shm_mounts();
+    let candidates = auto_candidates(&mounts, |path| available_space(path).ok());
+
+    match select_most_free(&candidates, required_space) {
+        Some(candidate) => {
+            info!(
+                "Selected {} out of {} candidates, it's the tmpfs with the most free space ({} \
+                 bytes)",
+                candidate.path.display(),
+                candidates.len(),
+                candidate.available
+            );
+
+            ShmDir { path: candidate.path.clone(), fs_type: Some(candidate.fs_type.clone()) }
+        }
+        None => {
+            warn!(
+                "None of the {} candidates is a tmpfs with {required_space} bytes of free space",
+                candidates.len()
+            );
+
+            let (shm_dir, reason) = select_shm_dir(&mounts, required_space);
+            info!("Selected {}, {reason}", shm_dir.path.display());
+
+            shm_dir
+        }
+    }
+}
+
+/// Collect the directories `--shm-dir auto` considers from the given mounts.
+///
+/// The `available` function returns the free space of a directory, directories
+/// for which it returns `None`, i.e. because they don't exist, are skipped.
+fn auto_candidates(mounts: &[Mount], available: impl Fn(&Path) -> Option<u64>) -> Vec<Candidate> {
+    let tmpfs_mounts = mounts.iter().filter(|mount| mount.fs_type == "tmpfs");
+
+    let paths = AUTO_CANDIDATES
+        .iter()
+        .map(PathBuf::from)
+        .chain(tmpfs_mounts.map(|mount| mount.mount_point.clone()));
+
+    let mut candidates: Vec<Candidate> = Vec::new();
+
+    for path in paths {
+        if candidates.iter().any(|candidate| candidate.path == path) {
+            continue;
+        }
+
+        let Some(mount) = mount_for_path(mounts, &path) else {
+            continue;
+        };
+
+        if let Some(available) = available(&path) {
+            candidates.push(Candidate { fs_type: mount.fs_type.clone(), path, available });
+        }
+    }
+
+    candidates
+}
+
+/// Select the tmpfs candidate with the most free space, if it has at least
+/// `required_space` bytes available.
+fn select_most_free(candidates: &[Candidate], required_space: u64) -> Option<&Candidate> {
+    candidates
+        .iter()
+        .filter(|candidate| candidate.fs_type == "tmpfs" && candidate.available >= required_space)
+        .max_by_key(|candidate| candidate.available)
+}
+
+/// Print all the tmpfs and ramfs mounts [`find_shm_dir()`] considers, their
+/// free space, and which one gets selected for the given `required_space`.
+///
+/// If the user selected a directory, `user_dir` should be set, no mount is
+/// selected in that case.
+///
+/// This is a diagnostic aid, so the report goes to stderr, keeping stdout
+/// clean for the output of `--dry-run`.
+pub fn print_mounts(required_space: u64, user_dir: Option<&Path>) {
+    let mounts = shm_mounts();
+
+    eprintln!("Memory backed mounts:");
+
+    for mount in mounts.iter().filter(|mount| mount.is_memory_backed()) {
+        let available = match available_space(&mount.mount_point) {
+            Ok(available) => format!("{available} bytes free"),
+            Err(e) => format!("unknown free space: {e}"),
+        };
+
+        eprintln!("    {} ({}, {available})", mount.mount_point.display(), mount.fs_type);
+    }
+
+    match user_dir {
+        Some(path) if is_auto(path) => {
+            let candidates = auto_candidates(&mounts, |path| available_space(path).ok());
+
+            eprintln!("Candidates for --shm-dir auto:");
+
+            for candidate in &candidates {
+                eprintln!(
+                    "    {} ({}, {} bytes free)",
+                    candidate.path.display(),
+                    candidate.fs_type,
+                    candidate.available
+                );
+            }
+
+            match select_most_free(&candidates, required_space) {
+                Some(candidate) => eprintln!(
+                    "Selected {}, it's the tmpfs with the most free space",
+                    candidate.path.display()
+                ),
+                None => {
+                    let (shm_dir, reason) = select_shm_dir(&mounts, required_space);
+                    eprintln!("Selected {}, {reason}", shm_dir.path.display());
+                }
+            }
+        }
+        Some(path) => eprintln!("Selected {}, it was given using --shm-dir", path.display()),
+        None => {
+            let (shm_dir, reason) = select_shm_dir(&mounts, required_space);
+            eprintln!("Selected {}, {reason}", shm_dir.path.display());
+        }
+    }
+}
+
+/// Get the mounts a shm directory can be selected from.
+///
+/// The mounts are read from `/proc/mounts`, unless it doesn't contain a tmpfs,
+/// in which case the memory backed entries of `/etc/fstab` are used.
+fn shm_mounts() -> Vec<Mount> {
+    match read_proc_mounts() {
+        Ok(mounts) if mounts.iter().any(Mount::is_memory_backed) => mounts,
+        Ok(_) => {
+            warn!("/proc/mounts doesn't contain a tmpfs, falling back to /etc/fstab");
+            fstab_mounts()
+        }
+        Err(e) => {
+            warn!("{e:#}, falling back to /etc/fstab");
+            fstab_mounts()
+        }
+    }
+}
+
+/// Select the shm directory from the given mounts.
+///
+/// Returns the selected directory and the reason why it was selected.
+fn select_shm_dir(mounts: &[Mount], required_space: u64) -> (ShmDir, &'static str) {
+    let default_path = PathBuf::from(TMPFS_PATH);
+    let default_mount = mount_for_path(mounts, &default_path);
+
+    if let Some(mount) = default_mount.filter(|mount| mount.is_memory_backed()) {
+        let shm_dir = ShmDir { path: default_path, fs_type: Some(mount.fs_type.clone()) };
+        return (shm_dir, "it's the default location and on a memory backed filesystem");
+    }
+
+    let has_enough_space = |mount: &&Mount| {
+        available_space(&mount.mount_point).is_ok_and(|available| available >= required_space)
+    };
+
+    if let Some(mount) = mounts.iter().filter(|m| m.is_memory_backed()).find(has_enough_space) {
+        let shm_dir =
+            ShmDir { path: mount.mount_point.clone(), fs_type: Some(mount.fs_type.clone()) };
+        return (shm_dir, "it's the first memory backed filesystem with enough free space");
+    }
+
+    warn!(
+        "Couldn't find a tmpfs filesystem with enough free space, falling back to \
+         {TMPFS_PATH}"
+    );
+
+    let shm_dir =
+        ShmDir { path: default_path, fs_type: default_mount.map(|mount| mount.fs_type.clone()) };
+
+    (shm_dir, "no memory backed filesystem with enough free space was found")
+}
+
+/// The space the maps take up on the filesystem of the shm directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpaceUsage {
+    /// The space that is available for the maps in bytes, this includes the
+    /// space the existing files of the maps take up, since they are reused or
+    /// replaced.
+    pub available: u64,
+    /// The space every map requires in bytes, in the same order as the maps.
+    pub required: Vec<u64>,
+}
+
+impl SpaceUsage {
+    /// The space all the maps together require in bytes.
+    pub fn total(&self) -> u64 {
+        self.required.iter().sum()
+    }
+
+    /// The index of the first map which doesn't fit into the available space
+    /// anymore, together with the maps before it.
+    pub fn first_overflow(&self) -> Option<usize> {
+        let mut total = 0;
+
+        self.required.iter().position(|required| {
+            total += required;
+            total > self.available
+        })
+    }
+}
+
+/// Calculate the space the given maps, whose files use the given prefix, take
+/// up in the given directory.
+///
+/// The files are extended to a multiple of the page size, so that's the space
+/// each map requires. Returns `None` if the free space of the filesystem
+/// couldn't be queried.
+pub fn space_usage(dir: &ShmDir, prefix: &str, maps: &[MapSpec]) -> Result<Option<SpaceUsage>> {
+    let available = match available_space(&dir.path) {
+        Ok(available) => available,
+        Err(e) => {
+            warn!("Couldn't check the free space of {}: {e:#}", dir.path.display());
+            return Ok(None);
+        }
+    };
+
+    let mut required = Vec::with_capacity(maps.len());
+    let mut existing = 0;
+
+    for map in maps {
+        required.push(round_to_page_size(map.size) as u64);
+        existing += existing_size(&dir.path.join(map.file_name(prefix)))?.unwrap_or_default();
+    }
+
+    Ok(Some(SpaceUsage { available: available + existing, required }))
+}
+
+/// Check that the filesystem of the given directory has enough free space for
+/// all the given maps, whose files use the given prefix.
+///
+/// Files of maps which already exist are reused or replaced, so the space they
+/// take up counts as available.
+pub fn check_free_space(dir: &ShmDir, prefix: &str, maps: &[MapSpec]) -> Result<()> {
+    let Some(usage) = space_usage(dir, prefix, maps)? else {
+        return Ok(());
+    };
+
+    let required = usage.total();
+
+    if required > usage.available {
+        bail!(
+            "Not enough free space in {}, the maps require {required} bytes but only {} bytes are \
+             available",
+            dir.path.display(),
+            usage.available
+        );
+    }
+
+    Ok(())
+}
+
+/// Get the memory backed filesystems from `/etc/fstab` whose mount point
+/// exists.
+fn fstab_mounts() -> Vec<Mount> {
+    match read_fstab() {
+        Ok(mounts) => mounts
+            .into_iter()
+            .filter(|mount| mount.is_memory_backed() && mount.mount_point.is_dir())
+            .collect(),
+        Err(e) => {
+            warn!("Couldn't search for a tmpfs filesystem: {e:#}");
+            Vec::new()
+        }
+    }
+}
+
+/// Check that the given directory is on a memory backed filesystem, i.e. a
+/// tmpfs or ramfs.
+///
+/// If it isn't, the mappings would end up on a disk backed filesystem, an error
+/// is returned unless `allow_non_tmpfs` is set.
+pub fn check_tmpfs(dir: &ShmDir, allow_non_tmpfs: bool) -> Result<()> {
+    // Checking the filesystem type using the Windows APIs is sadly problematic, I
+    // tried to use `GetVolumeInformationW` but, as the name suggest, it expects
+    // a volume, so `C:\\`, or as Wine exposes `/`, `Z:\\`. We can't check the
+    // file system name of `Z:\\dev\shm` for example. Even if we do check the
+    // filesystem name of `Z:\\` we get `NTFS` back.
+    //
+    // So we rely on the filesystem type we found in the mount table of the Linux
+    // side instead.
+    let path = dir.path.display();
+
+    match &dir.fs_type {
+        _ if dir.is_memory_backed() => Ok(()),
+        None => {
+            warn!("Couldn't verify that {path} is a tmpfs");
+            Ok(())
+        }
+        Some(fs_type) => {
+            warn!(
+                "{path} is not on a tmpfs or ramfs filesystem, the filesystem type is {fs_type}, \
+                 the mappings would be backed by a disk instead of memory"
+            );
+
+            if allow_non_tmpfs {
+                Ok(())
+            } else {
+                bail!(
+                    "The directory {path} is not on a tmpfs, use --allow-non-tmpfs to create the \
+                     mappings anyways"
+                )
+            }
+        }
+    }
+}
+
+/// Get the number of bytes that are available to us on the filesystem the
+/// given directory is on.
+pub fn available_space(dir: &Path) -> Result<u64> {
+    let dir_name = HSTRING::from(dir.as_os_str());
+    let mut available = 0;
+
+    // Wine implements this using `fstatvfs(3)` on the directory, so this works for
+    // Linux mount points as well.
+    unsafe { GetDiskFreeSpaceExW(&dir_name, Some(&mut available), None, None) }?;
+
+    Ok(available)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(mount_point: &str, fs_type: &str) -> Mount {
+        Mount { mount_point: mount_point.into(), fs_type: fs_type.to_owned() }
+    }
+
+    fn candidate(path: &str, fs_type: &str, available: u64) -> Candidate {
+        Candidate { path: path.into(), fs_type: fs_type.to_owned(), available }
+    }
+
+    #[test]
+    fn candidates_include_the_tmpfs_mounts() {
+        let mounts = [
+            mount("/", "ext4"),
+            mount("/dev/shm", "tmpfs"),
+            mount("/run", "tmpfs"),
+            mount("/tmp", "tmpfs"),
+            mount("/mnt/ram", "ramfs"),
+        ];
+
+        let candidates = auto_candidates(&mounts, |path| match path.to_str() {
+            Some("/dev/shm") => Some(1024),
+            Some("/run/shm") => Some(2048),
+            Some("/tmp") => Some(4096),
+            _ => None,
+        });
+
+        assert_eq!(
+            candidates,
+            [
+                candidate("/dev/shm", "tmpfs", 1024),
+                candidate("/run/shm", "tmpfs", 2048),
+                candidate("/tmp", "tmpfs", 4096),
+            ]
+        );
+    }
+
+    #[test]
+    fn candidates_keep_the_filesystem_type() {
+        let mounts = [mount("/", "ext4")];
+
+        let candidates = auto_candidates(&mounts, |_| Some(1024));
+
+        assert_eq!(
+            candidates,
+            [candidate("/dev/shm", "ext4", 1024), candidate("/run/shm", "ext4", 1024)]
+        );
+    }
+
+    #[test]
+    fn the_tmpfs_with_the_most_free_space_is_selected() {
+        let candidates = [
+            candidate("/dev/shm", "tmpfs", 1024),
+            candidate("/srv", "ext4", 8192),
+            candidate("/tmp", "tmpfs", 4096),
+        ];
+
+        assert_eq!(select_most_free(&candidates, 0), Some(&candidates[2]));
+        assert_eq!(select_most_free(&candidates, 4096), Some(&candidates[2]));
+        assert_eq!(select_most_free(&candidates, 4097), None);
+        assert_eq!(select_most_free(&[], 0), None);
+    }
+
+    #[test]
+    fn the_first_overflowing_map_is_found() {
+        let usage = SpaceUsage { available: 8192, required: vec![4096, 4096, 4096] };
+
+        assert_eq!(usage.total(), 12288);
+        assert_eq!(usage.first_overflow(), Some(2));
+
+        let usage = SpaceUsage { available: 8192, required: vec![4096, 4096] };
+        assert_eq!(usage.first_overflow(), None);
+    }
+
+    #[test]
+    fn huge_page_size() {
+        let meminfo = "MemTotal:       32594072 kB\nHugePages_Total:       0\nHugepagesize:       \
+                       2048 kB\nHugetlb:               0 kB\n";
+
+        assert_eq!(parse_huge_page_size(meminfo), Some(2 * 1024 * 1024));
+        assert_eq!(parse_huge_page_size("MemTotal:       32594072 kB\n"), None);
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Shutdown handling.
+//!
+//! Under Wine, the following ways of stopping the bridge make it unlink the
+//! `/dev/shm` backed files:
+//!
+//! * CTRL-C and CTRL-BREAK, Wine translates a `SIGINT` into a `CTRL_C_EVENT`.
+//! * Closing the console window, logging off, or shutting down the Wine
+//!   session, i.e. using `wineboot --end-session`, these produce a
+//!   `CTRL_CLOSE_EVENT`, `CTRL_LOGOFF_EVENT`, or `CTRL_SHUTDOWN_EVENT`
+//!   respectively.
+//!
+//! Wine doesn't translate a `SIGTERM` into a console event, it terminates the
+//! process right away, just like a `SIGKILL` does. In those cases the files
+//! need to be removed using the `clean` subcommand.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Condvar, Mutex,
+    },
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use windows::Win32::{
+    Foundation::{BOOL, TRUE},
+    System::Console::{
+        SetConsoleCtrlHandler, CTRL_CLOSE_EVENT, CTRL_LOGOFF_EVENT, CTRL_SHUTDOWN_EVENT,
+    },
+};
+
+/// How long we block the termination events to give the main thread a chance
+/// to clean up.
+///
+/// Windows terminates the process once the handler for one of those events
+/// returns, though it does so as well if the handler takes too long.
+const CLEAN_UP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Has somebody asked us to shut down.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+/// The thread waiting for a shutdown request.
+static WAITING_THREAD: Mutex<Option<Thread>> = Mutex::new(None);
+/// Has the main thread finished cleaning up.
+static FINISHED: Mutex<bool> = Mutex::new(false);
+static FINISHED_CONDVAR: Condvar = Condvar::new();
+
+/// Tracks if somebody asked us to shut down, i.e. by pressing CTRL-C.
+///
+/// Dropping the [`Shutdown`] signals that we finished cleaning up, so it
+/// should be kept alive until everything has been cleaned up.
+pub struct Shutdown {
+    _private: (),
+}
+
+/// Has a shutdown been requested.
+///
+/// This is always `false` if no [`Shutdown`] has been installed.
+pub fn is_requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+impl Shutdown {
+    /// Set a console control handler which will mark the shutdown as requested
+    /// and unpark the current thread.
+    pub fn install() -> Result<Self> {
+        *WAITING_THREAD.lock().expect("The lock should never be poisoned") =
+            Some(thread::current());
+
+        unsafe { SetConsoleCtrlHandler(Some(handler), TRUE) }
+            .context("Could not set up a console control handler")?;
+
+        Ok(Self { _private: () })
+    }
+
+    /// Create a [`Shutdown`] without a console control handler, used if
+    /// [`Shutdown::install()`] failed.
+    ///
+    /// The shutdown never gets requested, so waiting on it only ends once a
+    /// timeout elapses. CTRL-C terminates the process without giving us a
+    /// chance to clean up.
+    pub fn without_handler() -> Self {
+        Self { _private: () }
+    }
+
+    /// Has a shutdown been requested.
+    pub fn is_requested(&self) -> bool {
+        is_requested()
+    }
+
+    /// Park the current thread until a shutdown is requested.
+    pub fn wait(&self) {
+        while !self.is_requested() {
+            thread::park();
+        }
+    }
+
+    /// Park the current thread until a shutdown is requested or the timeout
+    /// elapses.
+    ///
+    /// Returns `true` if a shutdown has been requested.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        while !self.is_requested() {
+            let now = Instant::now();
+
+            if now >= deadline {
+                break;
+            }
+
+            thread::park_timeout(deadline - now);
+        }
+
+        self.is_requested()
+    }
+}
+
+impl Drop for Shutdown {
+    fn drop(&mut self) {
+        if let Ok(mut finished) = FINISHED.lock() {
+            *finished = true;
+            FINISHED_CONDVAR.notify_all();
+        }
+    }
+}
+
+unsafe extern "system" fn handler(ctrl_type: u32) -> BOOL {
+    REQUESTED.store(true, Ordering::SeqCst);
+
+    if let Some(thread) = WAITING_THREAD.lock().ok().as_deref().and_then(Option::as_ref) {
+        thread.unpark();
+    }
+
+    // For the termination events, the process gets terminated as soon as we
+    // return, so wait for the main thread to finish cleaning up.
+    if matches!(ctrl_type, CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT) {
+        if let Ok(finished) = FINISHED.lock() {
+            let _ = FINISHED_CONDVAR.wait_timeout_while(finished, CLEAN_UP_TIMEOUT, |f| !*f);
+        }
+    }
+
+    TRUE
+}
@@ -0,0 +1,178 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+/// The multiplier of the `K` suffix.
+fn base(si_units: bool) -> usize {
+    if si_units {
+        1000
+    } else {
+        1024
+    }
+}
+
+/// Parse a human-readable size into a number of bytes.
+///
+/// The size is a whole number optionally followed by a single `K`, `M`, or `G`
+/// suffix, the suffixes use binary multipliers, i.e. `1K` is 1024 bytes. A bare
+/// number is interpreted as a number of bytes.
+///
+/// Suffixes like `MB` or `MiB` are rejected since it's unclear if a decimal or
+/// a binary multiplier was meant.
+///
+/// This is meant to be used as a clap `value_parser`, so the error is a
+/// human-readable string.
+pub fn parse_size(input: &str) -> Result<usize, String> {
+    parse_size_with(input, false)
+}
+
+/// Parse a size like [`parse_size()`] does, the suffixes use decimal
+/// multipliers if `si_units` is set, i.e. `1K` is 1000 bytes.
+pub fn parse_size_with(input: &str, si_units: bool) -> Result<usize, String> {
+    let input = input.trim();
+
+    let digits_end = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+    let (number, suffix) = input.split_at(digits_end);
+
+    if number.is_empty() {
+        return Err(format!("The size `{input}` doesn't start with a number"));
+    }
+
+    let base = base(si_units);
+    let multiplier: usize = match suffix {
+        "" => 1,
+        "K" | "k" => base,
+        "M" | "m" => base * base,
+        "G" | "g" => base * base * base,
+        _ => {
+            return Err(format!(
+                "The size `{input}` has an unsupported suffix `{suffix}`, use a plain number of \
+                 bytes or one of the K, M or G suffixes, e.g. `32M`"
+            ))
+        }
+    };
+
+    let number: usize =
+        number.parse().map_err(|e| format!("The size `{input}` isn't a valid number: {e}"))?;
+
+    number.checked_mul(multiplier).ok_or_else(|| format!("The size `{input}` is too large"))
+}
+
+/// Format a number of bytes as a human-readable size using binary units, i.e.
+/// `48 MiB` or `1.5 KiB`.
+pub fn format_size(bytes: u64) -> String {
+    format_size_with(bytes, false)
+}
+
+/// Format a size like [`format_size()`] does, using decimal units like `48 MB`
+/// if `si_units` is set.
+pub fn format_size_with(bytes: u64, si_units: bool) -> String {
+    let units = if si_units { ["kB", "MB", "GB"] } else { ["KiB", "MiB", "GiB"] };
+    let base = base(si_units) as f64;
+
+    if (bytes as f64) < base {
+        return format!("{bytes} bytes");
+    }
+
+    let mut size = bytes as f64 / base;
+    let mut unit = units[0];
+
+    for next_unit in &units[1..] {
+        if size < base {
+            break;
+        }
+
+        size /= base;
+        unit = next_unit;
+    }
+
+    let size = format!("{size:.1}");
+
+    format!("{} {unit}", size.strip_suffix(".0").unwrap_or(&size))
+}
+
+/// A size given on the command line, whose suffix is only interpreted once
+/// it's known if the suffixes use decimal multipliers, see
+/// [`SizeArg::resolve()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeArg(String);
+
+impl SizeArg {
+    /// Check that the input is a valid size, see [`parse_size()`].
+    ///
+    /// This is meant to be used as a clap `value_parser`.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        parse_size(input)?;
+        Ok(Self(input.to_owned()))
+    }
+
+    /// Get the number of bytes, the suffix uses decimal multipliers if
+    /// `si_units` is set, see [`parse_size_with()`].
+    pub fn resolve(&self, si_units: bool) -> usize {
+        // Decimal multipliers are smaller than binary ones, so a size which parsed
+        // with binary multipliers can't overflow with decimal ones.
+        parse_size_with(&self.0, si_units).expect("The size was checked when it was parsed")
+    }
+}
+
+impl From<usize> for SizeArg {
+    fn from(bytes: usize) -> Self {
+        Self(bytes.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_suffixes() {
+        assert_eq!(parse_size_with("2048", false), Ok(2048));
+        assert_eq!(parse_size_with("2K", false), Ok(2048));
+        assert_eq!(parse_size_with("32M", false), Ok(32 * 1024 * 1024));
+        assert!(parse_size_with("32MB", false).is_err());
+    }
+
+    #[test]
+    fn decimal_suffixes() {
+        assert_eq!(parse_size_with("2048", true), Ok(2048));
+        assert_eq!(parse_size_with("2K", true), Ok(2000));
+        assert_eq!(parse_size_with("32M", true), Ok(32_000_000));
+        assert_eq!(parse_size_with("1G", true), Ok(1_000_000_000));
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(format_size_with(512, false), "512 bytes");
+        assert_eq!(format_size_with(1536, false), "1.5 KiB");
+        assert_eq!(format_size_with(48 * 1024 * 1024, false), "48 MiB");
+        assert_eq!(format_size_with(1500, true), "1.5 kB");
+        assert_eq!(format_size_with(48_000_000, true), "48 MB");
+    }
+
+    #[test]
+    fn deferred_sizes() {
+        let size = SizeArg::parse("2K").unwrap();
+
+        assert_eq!(size.resolve(false), 2048);
+        assert_eq!(size.resolve(true), 2000);
+        assert_eq!(SizeArg::from(16).resolve(true), 16);
+        assert!(SizeArg::parse("2KB").is_err());
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A JSON file describing the maps a running bridge manages.
+//!
+//! Unlike the `list` subcommand, which describes the files at the time it's
+//! run, the status file is kept up to date while the bridge is parked, so
+//! dashboards and other tools can watch it to find out which maps are live.
+
+use std::{
+    fs::{remove_file, rename},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::Bridge;
+
+/// The name of the status file inside the shm directory, if no path was
+/// given. The file prefix is prepended to it, so bridges using different
+/// prefixes don't overwrite each other's status, see [`default_path()`].
+pub const DEFAULT_NAME: &str = "shm-bridge.status";
+
+/// The content of the status file.
+#[derive(Debug, Serialize)]
+struct Status<'a> {
+    /// The process ID of the bridge, this is the Wine process ID.
+    pid: u32,
+    /// The directory the `/dev/shm` backed files of the maps are placed in.
+    dir: &'a Path,
+    /// When the status file was last written, in seconds since the UNIX epoch.
+    updated: u64,
+    /// The maps the bridge manages.
+    maps: Vec<MapEntry<'a>>,
+}
+
+/// A single map in the status file.
+#[derive(Debug, Serialize)]
+struct MapEntry<'a> {
+    name: &'a str,
+    size: usize,
+    path: PathBuf,
+    /// When the bridge created the mapping, in seconds since the UNIX epoch.
+    created: u64,
+}
+
+/// A status file which is removed once it's dropped.
+pub struct StatusFile {
+    path: PathBuf,
+    /// The maps we have written so far, together with the time their mapping
+    /// was created.
+    created: Vec<(String, SystemTime)>,
+}
+
+/// Get the path of the status file inside the given shm directory, for a
+/// bridge using the given file prefix, see [`DEFAULT_NAME`].
+pub fn default_path(dir: &Path, prefix: &str) -> PathBuf {
+    dir.join(format!("{prefix}{DEFAULT_NAME}"))
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default()
+}
+
+impl StatusFile {
+    /// Create a status file at the given path, nothing is written until
+    /// [`StatusFile::update()`] is called.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), created: Vec::new() }
+    }
+
+    /// The path of the status file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write the maps the bridge currently manages to the status file.
+    ///
+    /// Maps which weren't part of the last update get the current time as
+    /// their creation time. The file is written to a temporary file first and
+    /// then renamed, so readers never see a partially written file.
+    pub fn update(&mut self, bridge: &Bridge) -> Result<()> {
+        let now = SystemTime::now();
+        let maps = bridge.maps();
+
+        self.created.retain(|(name, _)| maps.iter().any(|map| &map.name == name));
+
+        for map in maps {
+            if !self.created.iter().any(|(name, _)| name == &map.name) {
+                self.created.push((map.name.clone(), now));
+            }
+        }
+
+        let status = Status {
+            pid: std::process::id(),
+            dir: bridge.dir(),
+            updated: unix_time(now),
+            maps: maps
+                .iter()
+                .map(|map| MapEntry {
+                    name: &map.name,
+                    size: map.size,
+                    path: bridge.file_path(map),
+                    created: self
+                        .created
+                        .iter()
+                        .find(|(name, _)| name == &map.name)
+                        .map(|(_, created)| unix_time(*created))
+                        .unwrap_or_default(),
+                })
+                .collect(),
+        };
+
+        let content =
+            serde_json::to_string_pretty(&status).expect("The status can always be serialized");
+
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+
+        std::fs::write(&temporary, content)
+            .and_then(|()| rename(&temporary, &self.path))
+            .with_context(|| format!("Could not write the status file {}", self.path.display()))
+    }
+}
+
+impl Drop for StatusFile {
+    fn drop(&mut self) {
+        // There's not much we can do if an error happens here, so let's ignore it.
+        let _ = remove_file(&self.path);
+    }
+}
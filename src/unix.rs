@@ -0,0 +1,154 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::{ffi::OsStr, path::Path, process::Command};
+
+use anyhow::{bail, Context, Result};
+
+/// Run a Linux executable and wait for it to finish.
+///
+/// Wine executes binaries which aren't Windows executables as native Linux
+/// processes, this allows us to do things the Windows APIs have no equivalent
+/// for, like changing the permissions of a file. The program needs to be an
+/// absolute Linux path, i.e. `/bin/chmod`, which Wine maps to the `Z:` drive.
+pub fn run<I, S>(program: &str, args: I) -> Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .with_context(|| format!("Could not run {program}"))?;
+
+    if !status.success() {
+        bail!("{program} failed with {status}");
+    }
+
+    Ok(())
+}
+
+/// Run a Linux executable, like [`run()`], and return what it printed to
+/// stdout.
+pub fn output<I, S>(program: &str, args: I) -> Result<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Could not run {program}"))?;
+
+    if !output.status.success() {
+        bail!("{program} failed with {}", output.status);
+    }
+
+    String::from_utf8(output.stdout).with_context(|| format!("{program} printed invalid UTF-8"))
+}
+
+/// Get the user ID and the user name of the owner of the given file.
+pub fn owner(path: &Path) -> Result<(u32, String)> {
+    let output = output("/usr/bin/stat", [OsStr::new("-c"), OsStr::new("%u %U"), path.as_os_str()])
+        .with_context(|| format!("Could not look up the owner of {}", path.display()))?;
+
+    let (uid, user) = output
+        .trim()
+        .split_once(' ')
+        .with_context(|| format!("Unexpected output of stat for {}: {output}", path.display()))?;
+    let uid = uid.parse().with_context(|| format!("Invalid user ID {uid}"))?;
+
+    Ok((uid, user.to_owned()))
+}
+
+/// Get the Linux user ID our process runs as.
+///
+/// Wine maps the Linux root directory to the `Z:` drive, so we can look it up
+/// in `/proc/self/status`.
+pub fn current_uid() -> Result<u32> {
+    /// The status file of our own process.
+    const STATUS_PATH: &str = "/proc/self/status";
+
+    let content = std::fs::read_to_string(STATUS_PATH)
+        .with_context(|| format!("Could not read {STATUS_PATH}"))?;
+
+    // The line has the `Uid: real effective saved filesystem` form.
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|uids| uids.split_whitespace().nth(1))
+        .and_then(|uid| uid.parse().ok())
+        .with_context(|| format!("Could not find the user ID in {STATUS_PATH}"))
+}
+
+/// Change the permission bits of the given file.
+pub fn chmod(path: &Path, mode: u32) -> Result<()> {
+    run("/bin/chmod", [OsStr::new(&format!("{mode:o}")), path.as_os_str()])
+        .with_context(|| format!("Could not change the mode of {} to {mode:o}", path.display()))
+}
+
+/// Change the group ownership of the given file.
+pub fn chgrp(path: &Path, gid: u32) -> Result<()> {
+    run("/bin/chgrp", [OsStr::new(&gid.to_string()), path.as_os_str()])
+        .with_context(|| format!("Could not change the group of {} to {gid}", path.display()))
+}
+
+/// Resolve a group name, or a numeric group ID, to a group ID.
+///
+/// We can't call into the Linux libc from inside Wine, so the group name is
+/// looked up in `/etc/group`.
+pub fn resolve_group(group: &str) -> Result<u32> {
+    /// The path of the group database.
+    const GROUP_PATH: &str = "/etc/group";
+
+    if let Ok(gid) = group.parse() {
+        return Ok(gid);
+    }
+
+    let content = std::fs::read_to_string(GROUP_PATH)
+        .with_context(|| format!("Could not read the group database {GROUP_PATH}"))?;
+
+    // Every line has the `name:password:gid:members` form.
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let gid = fields.nth(1)?.parse().ok()?;
+
+            (name == group).then_some(gid)
+        })
+        .next()
+        .with_context(|| format!("The group {group} doesn't exist in {GROUP_PATH}"))
+}
+
+/// Parse an octal file mode, i.e. `0660`.
+///
+/// This is meant to be used as a clap `value_parser`.
+pub fn parse_mode(input: &str) -> Result<u32, String> {
+    let mode = u32::from_str_radix(input, 8)
+        .map_err(|e| format!("The mode `{input}` isn't a valid octal number: {e}"))?;
+
+    if mode > 0o7777 {
+        return Err(format!("The mode `{input}` contains bits besides the permission bits"));
+    }
+
+    Ok(mode)
+}
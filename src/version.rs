@@ -0,0 +1,241 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! The machine-readable version information printed by `--version-json`.
+//!
+//! Launchers which bundle the bridge can use the list of features to find out
+//! what the installed binary supports, instead of guessing it from the version.
+
+use serde::Serialize;
+
+/// The features every build of the bridge supports.
+const FEATURES: &[&str] = &[
+    "config",
+    "json-config",
+    "presets",
+    "env-maps",
+    "clean",
+    "dump",
+    "list",
+    "check",
+    "json-output",
+    "pid-file",
+    "prefix",
+    "aliases",
+    "padding",
+    "verify",
+    "restart-on-crash",
+    "watch-adoption",
+    "space-report",
+    "snapshot",
+    "wait-for-consumer",
+    "lock",
+    "prune",
+    "glob",
+    "info",
+    "selftest",
+    "hooks",
+    "size-from",
+    "reload-config",
+    "hugepages",
+    "status-file",
+    "color",
+    "base-address",
+    "retries",
+    "attributes",
+    "best-effort",
+    "si-sizes",
+];
+
+/// The version information of the bridge.
+#[derive(Debug, Serialize)]
+struct VersionInfo {
+    /// The name of the crate.
+    name: &'static str,
+    /// The version of the crate.
+    version: &'static str,
+    /// The operating system the binary was built for.
+    os: &'static str,
+    /// The architecture the binary was built for.
+    arch: &'static str,
+    /// The supported features.
+    features: Vec<&'static str>,
+}
+
+impl VersionInfo {
+    fn new() -> Self {
+        let mut features = FEATURES.to_vec();
+
+        // Features which depend on how the binary was built.
+        if cfg!(debug_assertions) {
+            features.push("debug-assertions");
+        }
+
+        Self {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            features,
+        }
+    }
+}
+
+/// Print the version information as a single line of JSON to stdout.
+pub fn print_json() {
+    let json =
+        serde_json::to_string(&VersionInfo::new()).expect("The version info can be serialized");
+
+    println!("{json}");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use clap::CommandFactory;
+
+    use super::*;
+    use crate::cli::Cli;
+
+    /// The subcommands and the long flags which provide every feature, the
+    /// features without any are always active.
+    const FEATURE_FLAGS: &[(&str, &[&str])] = &[
+        ("config", &["config"]),
+        ("json-config", &["config-format"]),
+        ("presets", &["preset"]),
+        ("env-maps", &[]),
+        ("clean", &["clean"]),
+        ("dump", &["dump"]),
+        ("list", &["list"]),
+        ("check", &["check"]),
+        ("json-output", &["output"]),
+        ("pid-file", &["pid-file", "from-pid-file"]),
+        ("prefix", &["prefix"]),
+        ("aliases", &["alias"]),
+        ("padding", &["padding"]),
+        ("verify", &["verify"]),
+        ("restart-on-crash", &["restart-on-crash", "max-restarts"]),
+        ("watch-adoption", &["watch-adoption"]),
+        ("space-report", &["space-report"]),
+        ("snapshot", &["out"]),
+        ("wait-for-consumer", &["wait-for-consumer", "grace-period"]),
+        ("lock", &[]),
+        ("prune", &["prune"]),
+        ("glob", &["glob"]),
+        ("info", &["info"]),
+        ("selftest", &["selftest"]),
+        ("hooks", &["on-ready", "on-cleanup"]),
+        ("size-from", &["size-from", "size-width", "size-endianness"]),
+        ("reload-config", &["reload-config"]),
+        ("hugepages", &["hugepages"]),
+        ("status-file", &["status-file"]),
+        ("color", &["color"]),
+        ("base-address", &["base-address"]),
+        ("retries", &["retries", "retry-delay"]),
+        ("attributes", &["attributes"]),
+        ("best-effort", &["best-effort"]),
+        ("si-sizes", &["si-sizes"]),
+    ];
+
+    /// The subcommands and the long flags which aren't advertised as a feature.
+    const UNLISTED_FLAGS: &[&str] = &[
+        "help",
+        "version",
+        "version-json",
+        "create",
+        "shm-dir",
+        "verbose-mounts",
+        "verbose",
+        "quiet",
+        "map",
+        "size",
+        "size-all",
+        "from-stdin",
+        "dedupe",
+        "dry-run",
+        "force",
+        "migrate",
+        "sync",
+        "strict-sizes",
+        "quiet-on-reuse",
+        "no-zero",
+        "init-from",
+        "read-only",
+        "attach",
+        "mode",
+        "group",
+        "keep-on-exit",
+        "timeout",
+        "watch-maps",
+        "touch-interval",
+        "no-park",
+        "allow-non-tmpfs",
+        "max-size",
+        "allow-huge",
+        "bytes",
+        "interval",
+        "temporary",
+    ];
+
+    /// Collect the names of the subcommands and the long flags of the given
+    /// command, hidden flags are left out.
+    fn collect_flags(command: &clap::Command, flags: &mut BTreeSet<String>) {
+        for arg in command.get_arguments().filter(|arg| !arg.is_hide_set()) {
+            flags.extend(arg.get_long().map(str::to_owned));
+        }
+
+        for subcommand in command.get_subcommands() {
+            flags.insert(subcommand.get_name().to_owned());
+            collect_flags(subcommand, flags);
+        }
+    }
+
+    #[test]
+    fn features_match_the_flags() {
+        let mut flags = BTreeSet::new();
+        collect_flags(&Cli::command(), &mut flags);
+
+        for feature in FEATURES {
+            let (_, provided_by) = FEATURE_FLAGS
+                .iter()
+                .find(|(name, _)| name == feature)
+                .unwrap_or_else(|| panic!("The feature {feature} isn't backed by any flag"));
+
+            for flag in *provided_by {
+                assert!(flags.contains(*flag), "The feature {feature} needs the missing --{flag}");
+            }
+        }
+
+        for (feature, _) in FEATURE_FLAGS {
+            assert!(FEATURES.contains(feature), "The feature {feature} isn't advertised");
+        }
+
+        for flag in &flags {
+            let advertised =
+                FEATURE_FLAGS.iter().any(|(_, provided_by)| provided_by.contains(&flag.as_str()));
+
+            assert!(
+                advertised || UNLISTED_FLAGS.contains(&flag.as_str()),
+                "--{flag} needs to be part of a feature or added to the unlisted flags"
+            );
+        }
+    }
+}
@@ -0,0 +1,68 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Detect if we're running under Wine.
+//!
+//! The whole premise of the bridge is that the Windows side and the Linux side
+//! share a filesystem, which is only the case under Wine/Proton. On native
+//! Windows `/dev/shm` doesn't exist and creating the files would fail in
+//! confusing ways.
+
+use anyhow::Result;
+use windows::{
+    core::{s, w},
+    Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress},
+};
+
+use crate::error::Error;
+
+/// The signature of the `wine_get_version()` function Wine exports from
+/// `ntdll.dll`.
+type WineGetVersion = unsafe extern "C" fn() -> *const std::ffi::c_char;
+
+/// Get the version of Wine we're running under, or `None` if we're running on
+/// native Windows.
+///
+/// Wine exports a `wine_get_version()` function from its `ntdll.dll`, the
+/// native `ntdll.dll` has no such export.
+pub fn version() -> Option<String> {
+    // SAFETY: ntdll.dll is loaded into every process, so the handle stays valid
+    // for the lifetime of the process. If the export exists, it's Wine's
+    // `wine_get_version()` which takes no arguments and returns a pointer to a
+    // static NUL-terminated string.
+    unsafe {
+        let ntdll = GetModuleHandleW(w!("ntdll.dll")).ok()?;
+        let function: WineGetVersion =
+            std::mem::transmute(GetProcAddress(ntdll, s!("wine_get_version"))?);
+        let version = function();
+
+        if version.is_null() {
+            Some("unknown".to_owned())
+        } else {
+            Some(std::ffi::CStr::from_ptr(version).to_string_lossy().into_owned())
+        }
+    }
+}
+
+/// Make sure we're running under Wine and return its version, fails with
+/// [`Error::NotWine`] on native Windows.
+pub fn ensure_wine() -> Result<String> {
+    Ok(version().ok_or(Error::NotWine)?)
+}
@@ -0,0 +1,216 @@
+// Copyright (c) 2024 Damir Jelić
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Create and clean up mappings in a temporary directory.
+//!
+//! These tests need to run under Wine, Cargo uses it as the runner for the
+//! Windows target.
+//!
+//! They aren't run natively against a stubbed [`FileMapping`], the Win32 calls
+//! aren't limited to it, opening the files with their attributes, the console
+//! handler, and the Wine Unix calls are all part of the lifecycle as well. A
+//! stub would leave exactly the parts untested which differ between Wine
+//! versions.
+//!
+//! [`FileMapping`]: shm_bridge::file_mapping::FileMapping
+
+use std::{
+    fs::{create_dir_all, metadata, remove_dir_all},
+    path::{Path, PathBuf},
+};
+
+use shm_bridge::{
+    clean_up, clean_up_summary, error::Error, file_mapping::round_to_page_size, retry::RetryPolicy,
+    Bridge, BridgeError, MapSpec, MapStatus,
+};
+
+/// A temporary directory which is removed once it's dropped.
+struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("shm-bridge-{name}-{}", std::process::id()));
+        create_dir_all(&path).unwrap();
+
+        Self { path }
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.path);
+    }
+}
+
+/// Get a map name which is unique to this test run, mapping names are global.
+fn map_name(name: &str) -> String {
+    format!("shm-bridge-test-{name}-{}", std::process::id())
+}
+
+fn file_size(dir: &Path, name: &str) -> Option<u64> {
+    metadata(dir.join(name)).ok().map(|metadata| metadata.len())
+}
+
+#[test]
+fn mappings_are_created_and_cleaned_up() {
+    let dir = TestDir::new("create");
+    let (first, second) = (map_name("create-first"), map_name("create-second"));
+
+    let mut bridge = Bridge::new(dir.path());
+    bridge.add_map(&first, 2048).add_map(&second, 8192);
+    bridge.start().unwrap();
+
+    assert_eq!(file_size(dir.path(), &first), Some(round_to_page_size(2048) as u64));
+    assert_eq!(file_size(dir.path(), &second), Some(round_to_page_size(8192) as u64));
+
+    assert!(bridge.cleanup().is_clean());
+
+    assert_eq!(file_size(dir.path(), &first), None);
+    assert_eq!(file_size(dir.path(), &second), None);
+}
+
+#[test]
+fn dropping_the_bridge_removes_the_files() {
+    let dir = TestDir::new("drop");
+    let name = map_name("drop");
+
+    let mut bridge = Bridge::new(dir.path());
+    bridge.add_map(&name, 2048);
+    bridge.start().unwrap();
+
+    assert!(file_size(dir.path(), &name).is_some());
+
+    drop(bridge);
+
+    assert_eq!(file_size(dir.path(), &name), None);
+}
+
+#[test]
+fn kept_files_are_removed_by_clean_up() {
+    let dir = TestDir::new("keep");
+    let name = map_name("keep");
+
+    let mut bridge = Bridge::new(dir.path());
+    bridge.add_map(&name, 2048).keep_files(true);
+    bridge.start().unwrap();
+    drop(bridge);
+
+    assert!(file_size(dir.path(), &name).is_some());
+
+    let maps = [MapSpec::new(name.clone(), 2048)];
+    clean_up(dir.path(), "", &maps, RetryPolicy::default()).unwrap();
+
+    assert_eq!(file_size(dir.path(), &name), None);
+
+    // The file is gone, so a second clean up reports the map as missing.
+    let error = clean_up(dir.path(), "", &maps, RetryPolicy::default()).unwrap_err();
+    assert!(matches!(error, BridgeError::MissingMap { .. }));
+    assert_eq!(error.exit_code(), 3);
+}
+
+#[test]
+fn clean_up_summarizes_the_removed_and_absent_files() {
+    let dir = TestDir::new("summary");
+    let (present, absent) = (map_name("summary-present"), map_name("summary-absent"));
+
+    std::fs::write(dir.path().join(&present), [0; 16]).unwrap();
+
+    let maps = [MapSpec::new(present.clone(), 16), MapSpec::new(absent.clone(), 16)];
+    let summary = clean_up_summary(dir.path(), "", &maps, RetryPolicy::default());
+
+    assert_eq!(summary.removed, [present]);
+    assert_eq!(summary.absent.len(), 1);
+    assert!(summary.failed.is_empty());
+    assert!(!summary.is_clean());
+    assert_eq!(summary.into_result().unwrap_err().exit_code(), 3);
+}
+
+#[test]
+fn a_stuck_alias_doesnt_keep_the_files_around() {
+    let dir = TestDir::new("alias");
+    let (name, alias) = (map_name("alias"), map_name("alias-link"));
+
+    let mut bridge = Bridge::new(dir.path());
+    bridge.add_map(&name, 2048).add_alias(&name, &alias);
+    bridge.start().unwrap();
+
+    // A directory in place of the alias can't be removed as a file.
+    std::fs::remove_file(dir.path().join(&alias)).unwrap();
+    create_dir_all(dir.path().join(&alias).join("busy")).unwrap();
+
+    let summary = bridge.cleanup();
+
+    assert_eq!(summary.removed, [name.as_str()]);
+    assert_eq!(summary.failed.len(), 1);
+    assert_eq!(file_size(dir.path(), &name), None);
+}
+
+#[test]
+fn maps_are_added_and_removed_while_running() {
+    let dir = TestDir::new("reconcile");
+    let (first, second) = (map_name("reconcile-first"), map_name("reconcile-second"));
+
+    let mut bridge = Bridge::new(dir.path());
+    bridge.add_map(&first, 2048);
+    bridge.start().unwrap();
+
+    assert_eq!(bridge.start_map(MapSpec::new(second.clone(), 4096)).unwrap(), MapStatus::Created);
+    assert!(bridge.start_map(MapSpec::new(second.clone(), 4096)).is_err());
+    assert_eq!(file_size(dir.path(), &second), Some(round_to_page_size(4096) as u64));
+
+    let summary = bridge.stop_map(&first).unwrap();
+
+    assert!(summary.is_clean());
+    assert_eq!(file_size(dir.path(), &first), None);
+    assert_eq!(bridge.maps(), [MapSpec::new(second.clone(), 4096)]);
+
+    assert!(bridge.cleanup().is_clean());
+    assert_eq!(file_size(dir.path(), &second), None);
+}
+
+#[test]
+fn existing_files_with_a_different_size_are_refused() {
+    let dir = TestDir::new("mismatch");
+    let name = map_name("mismatch");
+
+    std::fs::write(dir.path().join(&name), [1; 100]).unwrap();
+
+    let mut bridge = Bridge::new(dir.path());
+    bridge.add_map(&name, 2048);
+
+    let error = bridge.start().unwrap_err();
+    assert!(matches!(error.downcast_ref::<Error>(), Some(Error::SizeMismatch { .. })));
+
+    // The existing file isn't ours, so it's left alone.
+    assert_eq!(file_size(dir.path(), &name), Some(100));
+
+    bridge.force(true);
+    bridge.start().unwrap();
+
+    assert_eq!(file_size(dir.path(), &name), Some(round_to_page_size(2048) as u64));
+
+    bridge.cleanup();
+}